@@ -1,83 +1,335 @@
-use jug_actix_lottery::eventbrite::model::{Event, Profile};
+use jug_actix_lottery::dedup::Dedup;
+use jug_actix_lottery::eventbrite::model::{Event, Profile, Attende};
 use jug_actix_lottery::eventbrite::errors::EventbriteError;
-use jug_actix_lottery::eventbrite::attendees::load_attendees;
-use jug_actix_lottery::eventbrite::events::get_current_event;
-use jug_actix_lottery::lottery::draw;
-use actix::{Actor, Context, Message, Handler};
-use actix::dev::{MessageResponse, ResponseChannel};
-use super::message::{GetAttendees, UpdateAttendeesResponse, UpdateAttendees, GetEvent};
+use jug_actix_lottery::source::AttendeeSource;
+use jug_actix_lottery::lottery::{seeded_draw_with, new_seed, exclude};
+use jug_actix_lottery::strategy::{DrawStrategy, Uniform};
+use actix::{fut, Actor, ActorFuture, Addr, Context, Message, MessageResult, Handler, ResponseActFuture};
+use tokio::prelude::Future;
+use tokio::prelude::future::join_all;
+use super::message::{GetAttendees, DrawResult, Freshness, WarmCache, UpdateAttendeesResponse, UpdateAttendees, AddOrderAttendees, ReplaceAttendee, GetEvent, GetEvents, CountAttendees, FindAttendee, AddAttendee, RemoveAttendee, ManualAttendee};
+use broadcast::actor::Broadcaster;
+use broadcast::message::{EventAttendees, LotteryEvent, Publish};
 use errors::LotteryError;
+use record::db::{DbExecutor, EventSnapshot, SaveAttendances, SaveManualAttendees, SaveSnapshots};
+use std::collections::HashMap;
+use std::time::Instant;
+use chrono::Utc;
+use uuid;
 
-pub struct LotteryCache {
-    attendees: Option<Vec<Profile>>,
-    event: Option<Event>,
+/// Attendees of a live event
+struct EventCache {
+    event: Event,
+    /// Attendees loaded from the source
+    loaded: Option<Vec<Attende>>,
+    /// Attendees added manually for the event
+    manual: Vec<ManualAttendee>,
+    /// Loaded and manual attendees merged, used for the draws
+    attendees: Option<Vec<Attende>>,
+    /// Last successful load of the attendees
+    loaded_at: Option<Instant>,
+    /// The last update failed, the attendees are the last ones successfully loaded
+    stale: bool,
 }
 
-impl Actor for LotteryCache {
+impl EventCache {
+    fn new(event: Event) -> Self {
+        EventCache { event: event, loaded: None, manual: vec![], attendees: None, loaded_at: None, stale: false }
+    }
+
+    /// Number of attendees used for the draws, None while the attendees were never loaded
+    fn count(&self) -> Option<EventAttendees> {
+        self.attendees.as_ref().map(|attendees| EventAttendees { event_id: self.event.id.clone(), attendees: attendees.iter().filter(|attendee| attendee.is_attending()).count(), stale: self.stale })
+    }
+
+    fn freshness(&self) -> Freshness {
+        Freshness { stale: self.stale, age: self.loaded_at.map(|loaded_at| loaded_at.elapsed()).unwrap_or_default() }
+    }
+
+    /// Replace the loaded attendee who is the same person, or add it if not loaded yet
+    ///
+    /// Ignored while the attendees were never loaded, the attendee will be part of the first load
+    fn upsert(&mut self, attendee: Attende) {
+        if let Some(ref mut loaded) = self.loaded {
+            match loaded.iter().position(|loaded| loaded.profile.same_person(&attendee.profile)) {
+                Some(position) => loaded[position] = attendee,
+                None => loaded.push(attendee)
+            }
+        }
+    }
+
+    /// Merge the manual attendees with the loaded ones, skipping the manual attendees already loaded
+    ///
+    /// Manual attendees are considered present at the event. Return true if the merged attendees changed
+    fn merge_attendees(&mut self) -> bool {
+        let manual = &self.manual;
+        let attendees = self.loaded.as_ref().map(|loaded| {
+            let mut attendees = loaded.clone();
+            attendees.extend(manual.iter()
+                .filter(|attendee| !loaded.iter().any(|loaded| loaded.profile.same_person(&attendee.profile)))
+                .map(|attendee| Attende::present(attendee.profile.clone())));
+            attendees
+        });
+        let changed = self.attendees != attendees;
+        self.attendees = attendees;
+        changed
+    }
+}
+
+pub struct LotteryCache<S: AttendeeSource> {
+    source: S,
+    /// Live events in the order of the source, the first one is the default event
+    events: Vec<Event>,
+    caches: HashMap<String, EventCache>,
+    db: Addr<DbExecutor>,
+    /// An update is running, the next ones are skipped until it completes
+    updating: bool,
+    /// Removal of the duplicated attendee records, applied on every load
+    dedup: Dedup,
+    /// Tell the connected clients when the attendees change
+    broadcaster: Addr<Broadcaster>,
+}
+
+impl<S: AttendeeSource + 'static> Actor for LotteryCache<S> {
     type Context = Context<Self>;
 }
 
-impl Default for LotteryCache {
-    fn default() -> Self {
-        LotteryCache { attendees: None, event: None }
+impl<S: AttendeeSource> LotteryCache<S> {
+    pub fn new(source: S, db: Addr<DbExecutor>, dedup: Dedup, broadcaster: Addr<Broadcaster>) -> Self {
+        LotteryCache { source: source, events: vec![], caches: HashMap::new(), db: db, updating: false, dedup: dedup, broadcaster: broadcaster }
+    }
+
+    /// Cache of the requested event, or of the default event if no event is requested
+    fn cache(&self, event_id: &Option<String>) -> Result<&EventCache, LotteryError> {
+        let event_id = match *event_id {
+            Some(ref event_id) => event_id,
+            None => &self.events.first().ok_or(LotteryError::NoEventAvailable)?.id
+        };
+        self.caches.get(event_id).ok_or(LotteryError::EventNotFound)
+    }
+
+    /// Replace the cached events and their attendees with the loaded ones
+    fn update_events(&mut self, events: Vec<Event>, loaded: Vec<Result<Vec<Attende>, failure::Error>>) -> UpdateAttendeesResponse {
+        self.caches.retain(|event_id, _| events.iter().any(|event| &event.id == event_id));
+        let mut failure = None;
+        let mut snapshots = vec![];
+        let mut removed_duplicates = 0;
+        let dedup = self.dedup;
+        for (position, (event, loaded)) in events.iter().zip(loaded).enumerate() {
+            {
+                let cache = self.caches.entry(event.id.clone()).or_insert_with(|| EventCache::new(event.clone()));
+                cache.event = event.clone();
+                match loaded {
+                    Ok(attendees) => {
+                        let (attendees, removed) = dedup.apply(attendees);
+                        removed_duplicates += removed;
+                        snapshots.push(EventSnapshot { position: position as i32, event: event.clone(), attendees: attendees.clone(), loaded_at: Utc::now().naive_utc(), manual: cache.manual.clone() });
+                        cache.loaded = Some(attendees);
+                        cache.loaded_at = Some(Instant::now());
+                        cache.stale = false;
+                    }
+                    Err(e) => {
+                        warn!("Keep the previous attendees of event {} : {}", event.id, e);
+                        cache.stale = true;
+                        failure = failure.or(Some(e));
+                    }
+                }
+            }
+            self.merge_attendees(&event.id);
+        }
+        let no_event = events.is_empty();
+        let live_events: Vec<String> = events.iter().map(|event| event.id.clone()).collect();
+        self.publish_attendees(&live_events);
+        self.db.do_send(SaveSnapshots { live_events: live_events, snapshots: snapshots });
+        self.events = events;
+        match failure {
+            Some(e) => update_error(e),
+            None if no_event => UpdateAttendeesResponse::NoEventAvailable,
+            None => UpdateAttendeesResponse::Updated { removed_duplicates: removed_duplicates }
+        }
+    }
+
+    /// Apply the attendees changed on the source since the last update to the cache of a live event
+    fn apply_changes(&mut self, event_id: &str, attendees: Vec<Attende>) -> Result<(), LotteryError> {
+        let dedup = self.dedup;
+        {
+            let cache = self.caches.get_mut(event_id).ok_or(LotteryError::EventNotFound)?;
+            for attendee in attendees {
+                cache.upsert(attendee);
+            }
+            cache.loaded = cache.loaded.take().map(|loaded| dedup.apply(loaded).0);
+        }
+        self.merge_attendees(event_id);
+        self.publish_attendees(&[event_id.to_owned()]);
+        Ok(())
+    }
+
+    /// Send the number of attendees of the events to the connected clients, the events never loaded are skipped
+    fn publish_attendees(&self, event_ids: &[String]) {
+        let events = event_ids.iter()
+            .filter_map(|event_id| self.caches.get(event_id))
+            .filter_map(|cache| cache.count())
+            .collect();
+        self.broadcaster.do_send(Publish(LotteryEvent::CacheRefreshed { events: events }));
+    }
+
+    /// Save the manual attendees of an event with its snapshot, so that they are kept on restart
+    fn save_manual_attendees(&self, event_id: &str) {
+        if let Some(cache) = self.caches.get(event_id) {
+            self.db.do_send(SaveManualAttendees { event_id: event_id.to_owned(), manual: cache.manual.clone() });
+        }
+    }
+
+    fn merge_attendees(&mut self, event_id: &str) {
+        if let Some(cache) = self.caches.get_mut(event_id) {
+            if cache.merge_attendees() {
+                if let Some(ref attendees) = cache.attendees {
+                    // The cancelled and refunded registrations do not count as attended events
+                    let profiles = attendees.iter()
+                        .filter(|attendee| attendee.is_attending())
+                        .map(|attendee| attendee.profile.clone())
+                        .collect();
+                    self.db.do_send(SaveAttendances { event_id: event_id.to_owned(), attendees: profiles });
+                }
+            }
+        }
     }
 }
 
 impl Message for UpdateAttendees {
-    type Result = UpdateAttendeesResponse;
+    type Result = Result<UpdateAttendeesResponse, ()>;
+}
+
+fn update_error(e: failure::Error) -> UpdateAttendeesResponse {
+    match e.downcast::<EventbriteError>() {
+        Ok(error) => match error {
+            EventbriteError::NoEventAvailable => UpdateAttendeesResponse::NoEventAvailable,
+            other_eventbrite_error => UpdateAttendeesResponse::EventbriteError { error: other_eventbrite_error }
+        },
+        Err(error) => UpdateAttendeesResponse::UnexpectedError { error: error }
+    }
 }
 
+impl<S: AttendeeSource + 'static> Handler<UpdateAttendees> for LotteryCache<S> {
+    type Result = ResponseActFuture<Self, UpdateAttendeesResponse, ()>;
 
-impl<A, M> MessageResponse<A, M> for UpdateAttendeesResponse
-    where
-        A: Actor,
-        M: Message<Result=UpdateAttendeesResponse>,
-{
-    fn handle<R: ResponseChannel<M>>(self, _: &mut A::Context, tx: Option<R>) {
-        if let Some(tx) = tx {
-            tx.send(self);
+    /// Load the events then the attendees of every event concurrently, the cache keeps serving during the update
+    fn handle(&mut self, _msg: UpdateAttendees, _ctx: &mut Context<Self>) -> Self::Result {
+        if self.updating {
+            return Box::new(fut::ok(UpdateAttendeesResponse::UpdateInProgress));
         }
+        self.updating = true;
+        let update = fut::wrap_future::<_, Self>(self.source.events())
+            .then(|events, actor, _ctx| -> ResponseActFuture<Self, UpdateAttendeesResponse, ()> {
+                match events {
+                    Ok(events) => {
+                        let loads: Vec<_> = events.iter()
+                            .map(|event| actor.source.attendees(event).then(|loaded| Ok::<_, ()>(loaded)))
+                            .collect();
+                        Box::new(fut::wrap_future(join_all(loads))
+                            .map(move |loaded, actor: &mut Self, _ctx| actor.update_events(events, loaded)))
+                    }
+                    Err(e) => {
+                        for cache in actor.caches.values_mut() {
+                            cache.stale = true;
+                        }
+                        Box::new(fut::ok(update_error(e)))
+                    }
+                }
+            })
+            .then(|response, actor, _ctx| {
+                actor.updating = false;
+                fut::result(response)
+            });
+        Box::new(update)
     }
 }
 
-impl Handler<UpdateAttendees> for LotteryCache {
-    type Result = UpdateAttendeesResponse;
+impl Message for WarmCache {
+    type Result = ();
+}
 
-    fn handle(&mut self, msg: UpdateAttendees, _ctx: &mut Context<Self>) -> Self::Result {
-        let load_attendees = get_current_event(&msg.organizer, &msg.token)
-            .and_then(|event| load_attendees(&event.id, &msg.token).map(|attendees| (event, attendees)));
-        match load_attendees {
-            Ok((event, attendees)) => {
-                self.attendees = Some(attendees);
-                self.event = Some(event);
-                UpdateAttendeesResponse::Updated
-            }
-            Err(e) => {
-                self.attendees = None;
-                match e.downcast::<EventbriteError>() {
-                    Ok(error) => match error {
-                        EventbriteError::NoEventAvailable => UpdateAttendeesResponse::NoEventAvailable,
-                        other_eventbrite_error => UpdateAttendeesResponse::EventbriteError { error: other_eventbrite_error }
-                    },
-                    Err(error) => UpdateAttendeesResponse::UnexpectedError { error: error }
-                }
-            }
+impl<S: AttendeeSource + 'static> Handler<WarmCache> for LotteryCache<S> {
+    type Result = ();
+
+    /// Fill the cache with the saved snapshots, ignored once the cache was updated from the source
+    fn handle(&mut self, msg: WarmCache, _ctx: &mut Context<Self>) -> Self::Result {
+        if !self.events.is_empty() {
+            return;
+        }
+        let now = Utc::now().naive_utc();
+        for snapshot in msg.snapshots {
+            let age = (now - snapshot.loaded_at).to_std().unwrap_or_default();
+            let event_id = snapshot.event.id.clone();
+            let mut cache = EventCache::new(snapshot.event.clone());
+            cache.loaded = Some(snapshot.attendees);
+            cache.manual = snapshot.manual;
+            cache.loaded_at = Instant::now().checked_sub(age).or(Some(Instant::now()));
+            cache.stale = true;
+            self.caches.insert(event_id.clone(), cache);
+            self.events.push(snapshot.event);
+            self.merge_attendees(&event_id);
         }
+        info!("Attendees cache warmed with the snapshots of {} events", self.events.len());
+    }
+}
+
+impl Message for AddOrderAttendees {
+    type Result = Result<(), LotteryError>;
+}
+
+impl<S: AttendeeSource + 'static> Handler<AddOrderAttendees> for LotteryCache<S> {
+    type Result = Result<(), LotteryError>;
+
+    fn handle(&mut self, msg: AddOrderAttendees, _ctx: &mut Context<Self>) -> Self::Result {
+        self.apply_changes(&msg.event_id, msg.attendees)
+    }
+}
+
+impl Message for ReplaceAttendee {
+    type Result = Result<(), LotteryError>;
+}
+
+impl<S: AttendeeSource + 'static> Handler<ReplaceAttendee> for LotteryCache<S> {
+    type Result = Result<(), LotteryError>;
+
+    fn handle(&mut self, msg: ReplaceAttendee, _ctx: &mut Context<Self>) -> Self::Result {
+        self.apply_changes(&msg.event_id, vec![msg.attendee])
     }
 }
 
 impl Message for GetAttendees {
-    type Result = Result<Vec<Profile>, LotteryError>;
+    type Result = Result<DrawResult, LotteryError>;
 }
 
-impl Handler<GetAttendees> for LotteryCache {
-    type Result = Result<Vec<Profile>, LotteryError>;
+impl<S: AttendeeSource + 'static> Handler<GetAttendees> for LotteryCache<S> {
+    type Result = Result<DrawResult, LotteryError>;
 
+    /// The seed is generated for every draw, a seed chosen by the client would let it look for the winners it wants
     fn handle(&mut self, msg: GetAttendees, _ctx: &mut Context<Self>) -> Self::Result {
-        self.attendees.as_ref()
+        let cache = self.cache(&msg.event_id)?;
+        let event_id = cache.event.id.clone();
+        let freshness = cache.freshness();
+        let seed = new_seed();
+        let strategy: &dyn DrawStrategy = match msg.weighted {
+            Some(ref weighted) => weighted,
+            None => &Uniform
+        };
+        cache.attendees.as_ref()
             .ok_or(LotteryError::NoEventAvailable)
-            .and_then(|ref attendees| draw(msg.nb, attendees).map_err(|error| LotteryError::DrawError { cause: error }))
-            .map(|attendees| attendees.into_iter().map(|r| r.clone()).collect())
+            .map(|attendees| attendees.iter()
+                .filter(|attendee| attendee.is_attending())
+                .filter(|attendee| !msg.checked_in_only || attendee.is_present())
+                .map(|attendee| attendee.profile.clone())
+                .collect())
+            .map(|attendees| exclude(&attendees, &msg.excluded))
+            .and_then(|attendees| {
+                let winners: Vec<Profile> = seeded_draw_with(msg.nb, &attendees, strategy, seed)
+                    .map(|winners| winners.into_iter().map(|r| r.clone()).collect())
+                    .map_err(|error| LotteryError::DrawError { cause: error })?;
+                let weights = msg.weighted.as_ref().map(|weighted| attendees.iter().map(|attendee| weighted.weight(attendee)).collect());
+                Ok(DrawResult { event_id: event_id, seed: seed, attendees: attendees, winners: winners, weights: weights, freshness: freshness })
+            })
     }
 }
 
@@ -85,12 +337,104 @@ impl Message for GetEvent {
     type Result = Result<Event, LotteryError>;
 }
 
-impl Handler<GetEvent> for LotteryCache {
+impl<S: AttendeeSource + 'static> Handler<GetEvent> for LotteryCache<S> {
     type Result = Result<Event, LotteryError>;
 
-    fn handle(&mut self, _msg: GetEvent, _ctx: &mut Context<Self>) -> Self::Result {
-        self.event.as_ref()
-            .ok_or(LotteryError::NoEventAvailable)
-            .map(|event| event.clone())
+    fn handle(&mut self, msg: GetEvent, _ctx: &mut Context<Self>) -> Self::Result {
+        self.cache(&msg.event_id)
+            .map(|cache| cache.event.clone())
+    }
+}
+
+impl Message for GetEvents {
+    type Result = Vec<Event>;
+}
+
+impl<S: AttendeeSource + 'static> Handler<GetEvents> for LotteryCache<S> {
+    type Result = MessageResult<GetEvents>;
+
+    fn handle(&mut self, _msg: GetEvents, _ctx: &mut Context<Self>) -> Self::Result {
+        MessageResult(self.events.clone())
+    }
+}
+
+impl Message for CountAttendees {
+    type Result = Result<EventAttendees, LotteryError>;
+}
+
+impl<S: AttendeeSource + 'static> Handler<CountAttendees> for LotteryCache<S> {
+    type Result = Result<EventAttendees, LotteryError>;
+
+    fn handle(&mut self, msg: CountAttendees, _ctx: &mut Context<Self>) -> Self::Result {
+        self.cache(&msg.event_id)?.count().ok_or(LotteryError::NoEventAvailable)
+    }
+}
+
+impl Message for FindAttendee {
+    type Result = Result<(String, Profile), LotteryError>;
+}
+
+impl<S: AttendeeSource + 'static> Handler<FindAttendee> for LotteryCache<S> {
+    type Result = Result<(String, Profile), LotteryError>;
+
+    /// Namesakes without id cannot be told apart, the attendee is then refused rather than guessed.
+    /// The cancelled and refunded attendees cannot win, so they are not found
+    fn handle(&mut self, msg: FindAttendee, _ctx: &mut Context<Self>) -> Self::Result {
+        let cache = self.cache(&msg.event_id)?;
+        let attendees = cache.attendees.as_ref().ok_or(LotteryError::NoEventAvailable)?;
+        let found: Vec<&Profile> = attendees.iter()
+            .filter(|attendee| attendee.is_attending())
+            .map(|attendee| &attendee.profile)
+            .filter(|profile| match msg.attendee_id {
+                Some(ref attendee_id) => profile.id.as_ref() == Some(attendee_id),
+                None => profile.first_name == msg.first_name && profile.last_name == msg.last_name
+            })
+            .collect();
+        match found.as_slice() {
+            [profile] => Ok((cache.event.id.clone(), (*profile).clone())),
+            [] => Err(LotteryError::AttendeeNotFound),
+            _ => Err(LotteryError::InvalidParameter)
+        }
     }
-}
\ No newline at end of file
+}
+
+impl Message for AddAttendee {
+    type Result = Result<ManualAttendee, LotteryError>;
+}
+
+impl<S: AttendeeSource + 'static> Handler<AddAttendee> for LotteryCache<S> {
+    type Result = Result<ManualAttendee, LotteryError>;
+
+    fn handle(&mut self, msg: AddAttendee, _ctx: &mut Context<Self>) -> Self::Result {
+        let event_id = self.cache(&msg.event_id)?.event.id.clone();
+        let attendee = ManualAttendee { id: format!("{}", uuid::Uuid::new_v4()), profile: msg.profile };
+        if let Some(cache) = self.caches.get_mut(&event_id) {
+            cache.manual.push(attendee.clone());
+        }
+        self.merge_attendees(&event_id);
+        self.save_manual_attendees(&event_id);
+        self.publish_attendees(&[event_id]);
+        Ok(attendee)
+    }
+}
+
+impl Message for RemoveAttendee {
+    type Result = Result<ManualAttendee, LotteryError>;
+}
+
+impl<S: AttendeeSource + 'static> Handler<RemoveAttendee> for LotteryCache<S> {
+    type Result = Result<ManualAttendee, LotteryError>;
+
+    fn handle(&mut self, msg: RemoveAttendee, _ctx: &mut Context<Self>) -> Self::Result {
+        let (event_id, attendee) = self.caches.iter_mut()
+            .filter_map(|(event_id, cache)| cache.manual.iter()
+                .position(|attendee| attendee.id == msg.id)
+                .map(|position| (event_id.clone(), cache.manual.remove(position))))
+            .next()
+            .ok_or(LotteryError::AttendeeNotFound)?;
+        self.merge_attendees(&event_id);
+        self.save_manual_attendees(&event_id);
+        self.publish_attendees(&[event_id]);
+        Ok(attendee)
+    }
+}
@@ -1,24 +1,38 @@
 use actix::prelude::Addr;
-use tokio::timer::Interval;
+use tokio::timer::Delay;
 use std::time::Instant;
-use tokio::prelude::future::Future;
-use tokio::prelude::Stream;
-use core::time::Duration;
+use tokio::prelude::future::{loop_fn, Future, Loop};
+use chrono::Utc;
 
+use jug_actix_lottery::schedule::RefreshSchedule;
+use jug_actix_lottery::source::AttendeeSource;
 use attendees::actor::LotteryCache;
-use attendees::message::{UpdateAttendees, UpdateAttendeesResponse};
+use attendees::message::{GetEvents, UpdateAttendees, UpdateAttendeesResponse};
 
-pub fn cache_update_interval(duration: u64, addr: Addr<LotteryCache>, token: String, organizer: String) -> impl Future<Item=(), Error=()> + 'static {
-    Interval::new(Instant::now(), Duration::from_secs(duration))
-        .then(move |_instant| addr.send(UpdateAttendees { token: token.clone(), organizer: organizer.clone() })
-            .map_err(|err| error!("Error on sending update message : {:?}", err)))
-        .for_each(move |res| {
-            match res {
-                UpdateAttendeesResponse::Updated => info!("Attendees cache updated"),
-                UpdateAttendeesResponse::NoEventAvailable => info!("No event available on eventbrite"),
-                UpdateAttendeesResponse::EventbriteError { error: ref e } => info!("Error on eventbrite : {}", e),
-                UpdateAttendeesResponse::UnexpectedError { error: ref e } => error!("Unexpected error on update attendees \n{:?}", e)
-            };
-            Ok(())
-        })
-}
\ No newline at end of file
+/// Update the attendees cache forever, waiting between the updates the delay given by the schedule for the live events
+pub fn cache_update_loop<S: AttendeeSource + 'static>(schedule: RefreshSchedule, addr: Addr<LotteryCache<S>>) -> impl Future<Item=(), Error=()> + 'static {
+    loop_fn((), move |_| {
+        let schedule = schedule.clone();
+        let events_addr = addr.clone();
+        addr.send(UpdateAttendees {})
+            .map(|res| {
+                match res {
+                    Ok(UpdateAttendeesResponse::Updated { removed_duplicates }) => info!("Attendees cache updated, {} duplicates removed", removed_duplicates),
+                    Ok(UpdateAttendeesResponse::UpdateInProgress) => info!("Attendees cache update still in progress"),
+                    Ok(UpdateAttendeesResponse::NoEventAvailable) => info!("No event available on eventbrite"),
+                    Ok(UpdateAttendeesResponse::EventbriteError { error: ref e }) => info!("Error on eventbrite : {}", e),
+                    Ok(UpdateAttendeesResponse::UnexpectedError { error: ref e }) => error!("Unexpected error on update attendees \n{:?}", e),
+                    Err(()) => error!("Attendees cache update aborted")
+                };
+            })
+            .and_then(move |_| events_addr.send(GetEvents {}))
+            .map_err(|err| error!("Error on sending update message : {:?}", err))
+            .and_then(move |events| {
+                let delay = schedule.next_delay(&events, Utc::now());
+                debug!("Next attendees cache update in {:?}", delay);
+                Delay::new(Instant::now() + delay)
+                    .map_err(|err| error!("Error on attendees cache update timer : {:?}", err))
+            })
+            .map(|_| Loop::<(), ()>::Continue(()))
+    })
+}
@@ -1,23 +1,120 @@
 use jug_actix_lottery::eventbrite::errors::EventbriteError;
+use jug_actix_lottery::eventbrite::model::{Attende, Profile};
+use jug_actix_lottery::strategy::Weighted;
+use record::db::EventSnapshot;
+use std::time::Duration;
+use std::fmt::Display;
+use serde::Serializer;
 
-pub struct UpdateAttendees {
-    pub organizer: String,
-    pub token: String,
-}
+pub struct UpdateAttendees {}
 
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
 pub enum UpdateAttendeesResponse {
-    Updated,
+    Updated {
+        /// Duplicated attendee records removed from the loaded attendees of all the events
+        removed_duplicates: usize
+    },
+    /// Another update is still running
+    UpdateInProgress,
     NoEventAvailable,
     EventbriteError {
+        #[serde(serialize_with = "display")]
         error: EventbriteError
     },
     UnexpectedError {
+        #[serde(serialize_with = "display")]
         error: failure::Error
     },
 }
 
+/// Serialize an error with its message
+fn display<T: Display, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
 pub struct GetAttendees {
-    pub nb: i8
+    pub event_id: Option<String>,
+    pub nb: i8,
+    pub excluded: Vec<Profile>,
+    pub weighted: Option<Weighted>,
+    pub checked_in_only: bool,
+}
+
+/// Result of a draw with every input needed to replay it
+pub struct DrawResult {
+    pub event_id: String,
+    pub seed: u64,
+    pub attendees: Vec<Profile>,
+    pub winners: Vec<Profile>,
+    pub weights: Option<Vec<f64>>,
+    pub freshness: Freshness,
+}
+
+/// Freshness of the attendees used for a draw
+#[derive(Clone, Copy, Debug)]
+pub struct Freshness {
+    /// The last update of the attendees failed, the previous ones were used
+    pub stale: bool,
+    /// Time since the attendees were loaded from the source
+    pub age: Duration,
+}
+
+/// Fill the cache with the attendees saved before a restart
+pub struct WarmCache {
+    pub snapshots: Vec<EventSnapshot>
+}
+
+/// Add the attendees of an order placed since the last update, received from a webhook
+pub struct AddOrderAttendees {
+    pub event_id: String,
+    pub attendees: Vec<Attende>
+}
+
+/// Replace an attendee updated or checked in since the last update, received from a webhook
+pub struct ReplaceAttendee {
+    pub event_id: String,
+    pub attendee: Attende
+}
+
+/// Get a live event, the default event if no event id is provided
+pub struct GetEvent {
+    pub event_id: Option<String>
 }
 
-pub struct GetEvent {}
\ No newline at end of file
+/// Get all the live events
+pub struct GetEvents {}
+
+/// Count the attendees of a live event, the default event if no event id is provided
+pub struct CountAttendees {
+    pub event_id: Option<String>
+}
+
+/// Find an attendee of a live event by its eventbrite id, or by its names when no id is provided
+///
+/// Return the id of the event and the profile of the attendee
+pub struct FindAttendee {
+    pub event_id: Option<String>,
+    pub attendee_id: Option<String>,
+    pub first_name: String,
+    pub last_name: String
+}
+
+/// Attendee added manually, not known by the attendee source
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ManualAttendee {
+    pub id: String,
+    #[serde(flatten)]
+    pub profile: Profile,
+}
+
+/// Add an attendee to an event, the default event if no event id is provided
+pub struct AddAttendee {
+    pub event_id: Option<String>,
+    pub profile: Profile
+}
+
+/// Remove an attendee added manually
+pub struct RemoveAttendee {
+    pub id: String
+}
@@ -0,0 +1,71 @@
+use actix::{Actor, Context, Handler, Message, MessageResult, Recipient};
+use actix::prelude::SendError;
+use serde_json;
+use std::collections::HashMap;
+use super::message::{Broadcast, Publish, Subscribe, Unsubscribe};
+
+/// Clients listening to the lottery events, like the projector and the phones of the attendees
+#[derive(Default)]
+pub struct Broadcaster {
+    clients: HashMap<usize, Recipient<Broadcast>>,
+    next_id: usize,
+}
+
+impl Actor for Broadcaster {
+    type Context = Context<Self>;
+}
+
+impl Message for Broadcast {
+    type Result = ();
+}
+
+impl Message for Subscribe {
+    type Result = usize;
+}
+
+impl Handler<Subscribe> for Broadcaster {
+    type Result = MessageResult<Subscribe>;
+
+    fn handle(&mut self, msg: Subscribe, _ctx: &mut Context<Self>) -> Self::Result {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.clients.insert(id, msg.client);
+        debug!("Client {} subscribed, {} clients listening", id, self.clients.len());
+        MessageResult(id)
+    }
+}
+
+impl Message for Unsubscribe {
+    type Result = ();
+}
+
+impl Handler<Unsubscribe> for Broadcaster {
+    type Result = ();
+
+    fn handle(&mut self, msg: Unsubscribe, _ctx: &mut Context<Self>) -> Self::Result {
+        self.clients.remove(&msg.id);
+    }
+}
+
+impl Message for Publish {
+    type Result = ();
+}
+
+impl Handler<Publish> for Broadcaster {
+    type Result = ();
+
+    /// Send the event to every client, the clients which are gone are removed and the slow ones miss the event
+    fn handle(&mut self, msg: Publish, _ctx: &mut Context<Self>) -> Self::Result {
+        let json = match serde_json::to_string(&msg.0) {
+            Ok(json) => json,
+            Err(e) => {
+                error!("Error on serializing lottery event {:?} : {}", msg.0, e);
+                return;
+            }
+        };
+        self.clients.retain(|_, client| match client.do_send(Broadcast(json.clone())) {
+            Err(SendError::Closed(_)) => false,
+            _ => true
+        });
+    }
+}
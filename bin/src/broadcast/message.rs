@@ -0,0 +1,52 @@
+use actix::Recipient;
+use jug_actix_lottery::eventbrite::model::Profile;
+use record::models::Winner;
+
+/// What happened in the lottery, sent as JSON to the connected clients
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LotteryEvent {
+    /// Winners drawn, recorded or not
+    Draw {
+        draw_id: String,
+        event_id: String,
+        winners: Vec<Profile>
+    },
+    /// Attendees of live events updated from the source or from a webhook
+    CacheRefreshed {
+        events: Vec<EventAttendees>
+    },
+    /// Winners recorded by `/record`, `/draws` or `/prizes/draw`
+    WinnersRecorded {
+        winners: Vec<Winner>
+    },
+    /// Record of a winner undone by `DELETE /record/{id}`
+    WinnerDeleted {
+        winner: Winner
+    },
+}
+
+/// Number of attendees of a live event, as used by the draws
+#[derive(Serialize, Debug)]
+pub struct EventAttendees {
+    pub event_id: String,
+    pub attendees: usize,
+    /// The last update failed, the attendees are the last ones successfully loaded
+    pub stale: bool,
+}
+
+/// Send an event to all the connected clients
+pub struct Publish(pub LotteryEvent);
+
+/// Register a client, the result is the id used to unsubscribe
+pub struct Subscribe {
+    pub client: Recipient<Broadcast>
+}
+
+/// Remove a disconnected client
+pub struct Unsubscribe {
+    pub id: usize
+}
+
+/// Event serialized once for all the clients
+pub struct Broadcast(pub String);
@@ -0,0 +1,3 @@
+pub mod actor;
+pub mod message;
+pub mod socket;
\ No newline at end of file
@@ -0,0 +1,82 @@
+use actix::{fut, Actor, ActorContext, ActorFuture, Addr, AsyncContext, ContextFutureSpawner, Handler, Running, StreamHandler, WrapFuture};
+use actix_web::ws;
+use std::time::{Duration, Instant};
+use super::actor::Broadcaster;
+use super::message::{Broadcast, Subscribe, Unsubscribe};
+use WebState;
+
+/// Delay between the pings sent to the client
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// The connection is closed when the client did not answer for this delay, like a phone leaving the meetup
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// WebSocket connection of a client, forwarding the lottery events as JSON text messages
+pub struct DrawSocket {
+    broadcaster: Addr<Broadcaster>,
+    /// Id given by the broadcaster once subscribed
+    id: Option<usize>,
+    /// Last message received from the client
+    heartbeat: Instant,
+}
+
+impl DrawSocket {
+    pub fn new(broadcaster: Addr<Broadcaster>) -> Self {
+        DrawSocket { broadcaster: broadcaster, id: None, heartbeat: Instant::now() }
+    }
+
+    fn heartbeat(&self, ctx: &mut ws::WebsocketContext<Self, WebState>) {
+        ctx.run_interval(HEARTBEAT_INTERVAL, |socket, ctx| {
+            if socket.heartbeat.elapsed() > CLIENT_TIMEOUT {
+                debug!("WebSocket client timed out");
+                ctx.stop();
+            } else {
+                ctx.ping("");
+            }
+        });
+    }
+}
+
+impl Actor for DrawSocket {
+    type Context = ws::WebsocketContext<Self, WebState>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.heartbeat(ctx);
+        self.broadcaster.send(Subscribe { client: ctx.address().recipient() })
+            .into_actor(self)
+            .then(|result, socket, ctx| {
+                match result {
+                    Ok(id) => socket.id = Some(id),
+                    Err(_) => ctx.stop()
+                }
+                fut::ok(())
+            })
+            .wait(ctx);
+    }
+
+    fn stopping(&mut self, _ctx: &mut Self::Context) -> Running {
+        if let Some(id) = self.id {
+            self.broadcaster.do_send(Unsubscribe { id: id });
+        }
+        Running::Stop
+    }
+}
+
+/// The clients only listen, their text and binary messages are ignored
+impl StreamHandler<ws::Message, ws::ProtocolError> for DrawSocket {
+    fn handle(&mut self, msg: ws::Message, ctx: &mut Self::Context) {
+        self.heartbeat = Instant::now();
+        match msg {
+            ws::Message::Ping(message) => ctx.pong(&message),
+            ws::Message::Close(_) => ctx.stop(),
+            ws::Message::Pong(_) | ws::Message::Text(_) | ws::Message::Binary(_) => ()
+        }
+    }
+}
+
+impl Handler<Broadcast> for DrawSocket {
+    type Result = ();
+
+    fn handle(&mut self, msg: Broadcast, ctx: &mut Self::Context) -> Self::Result {
+        ctx.text(msg.0);
+    }
+}
@@ -1,3 +1,4 @@
+use diesel;
 use failure::Error;
 
 #[derive(Fail, Debug)]
@@ -6,8 +7,31 @@ pub enum LotteryError {
     InvalidParameter,
     #[fail(display = "No event available")]
     NoEventAvailable,
+    #[fail(display = "Event not found")]
+    EventNotFound,
+    #[fail(display = "Draw not found")]
+    DrawNotFound,
+    #[fail(display = "Prize not found")]
+    PrizeNotFound,
+    #[fail(display = "No unit of the prize left")]
+    PrizeSoldOut,
+    #[fail(display = "Prize already awarded")]
+    PrizeAwarded,
+    #[fail(display = "Winner not found")]
+    WinnerNotFound,
+    #[fail(display = "Attendee not found")]
+    AttendeeNotFound,
+    #[fail(display = "Unauthorized")]
+    Unauthorized,
     #[fail(display = "Error during attendees draw")]
     DrawError { cause: Error },
+    #[fail(display = "Error while calling eventbrite")]
+    EventbriteError { cause: Error },
     #[fail(display = "Unexpected error")]
     UnexpectedError { cause: Error },
-}
\ No newline at end of file
+}
+impl From<diesel::result::Error> for LotteryError {
+    fn from(error: diesel::result::Error) -> Self {
+        LotteryError::UnexpectedError { cause: error.into() }
+    }
+}
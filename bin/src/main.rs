@@ -10,6 +10,8 @@ extern crate failure;
 extern crate core;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde;
+extern crate serde_json;
 #[macro_use]
 extern crate log;
 extern crate env_logger;
@@ -19,34 +21,61 @@ extern crate diesel;
 extern crate diesel_migrations;
 extern crate uuid;
 extern crate r2d2;
+extern crate chrono;
 
 mod attendees;
+mod broadcast;
 mod errors;
 mod record;
 
 use actix::prelude::{System, Arbiter, Addr, Actor};
 use attendees::actor::LotteryCache;
-use attendees::message::{GetAttendees, GetEvent};
-use attendees::cache_loop::cache_update_interval;
+use attendees::message::{GetAttendees, GetEvent, GetEvents, CountAttendees, FindAttendee, DrawResult, Freshness, UpdateAttendees, UpdateAttendeesResponse, WarmCache, AddOrderAttendees, ReplaceAttendee, AddAttendee, RemoveAttendee};
+use attendees::cache_loop::cache_update_loop;
+use broadcast::actor::Broadcaster;
+use broadcast::message::{LotteryEvent, Publish};
+use broadcast::socket::DrawSocket;
 use errors::LotteryError;
 use tokio::prelude::future;
 use tokio::prelude::future::Future;
 
-use actix_web::{App, HttpResponse, FutureResponse, State, AsyncResponder, Query, Json};
-use actix_web::{http, error, middleware};
+use actix_web::{App, HttpRequest, HttpResponse, FutureResponse, State, AsyncResponder, Query, Json, Path};
+use actix_web::{http, error, middleware, ws};
+use actix_web::dev::HttpResponseBuilder;
 use actix_web::server::HttpServer;
 use std::env;
+use std::time::Duration;
 
 use diesel::prelude::*;
 use diesel::r2d2::ConnectionManager;
-use record::db::{DbExecutor, CreateWinner};
+use record::db::{BusyTimeout, DbExecutor, CreateWinner, GetWinners, SearchWinners, DeleteWinner, CreateDraw, RecordDraw, GetDraw, GetParticipations, GetSnapshots};
+use record::models::{Draw, Prize, Winner};
+use record::prizes::{CreatePrize, GetPrizes, GetPrize, UpdatePrize, DeletePrize, GetAvailablePrizes, AwardPrizes};
+use jug_actix_lottery::eventbrite::{EventbriteClient, EVENTBRITE_BASE_URL};
+use jug_actix_lottery::eventbrite::webhooks::{WebhookPayload, WebhookResource, load_order, load_attendee, valid_secret};
+use jug_actix_lottery::file::FileSource;
+use jug_actix_lottery::source::AttendeeSource;
+use jug_actix_lottery::eventbrite::model::Profile;
+use jug_actix_lottery::lottery::{attendees_hash, seeded_draw_with};
+use jug_actix_lottery::strategy::{DrawStrategy, Uniform, Weighted};
+use jug_actix_lottery::schedule::RefreshSchedule;
+use jug_actix_lottery::dedup::Dedup;
 use actix::SyncArbiter;
 
 embed_migrations!("../migrations");
 
-struct WebState {
-    cache: Addr<LotteryCache>,
+/// Draw screen for the projector, embedded in the binary
+const PROJECTOR_PAGE: &'static str = include_str!("../static/projector.html");
+
+#[derive(Clone)]
+pub struct WebState {
+    cache: Addr<LotteryCache<Box<dyn AttendeeSource>>>,
     db: Addr<DbExecutor>,
+    /// Send the draws and the recorded winners to the WebSocket clients
+    broadcaster: Addr<Broadcaster>,
+    /// Client used to load the objects changed by the webhooks, None when the attendees are loaded from a file
+    eventbrite: Option<EventbriteClient>,
+    webhook_secret: Option<String>,
 }
 
 impl error::ResponseError for LotteryError {
@@ -54,56 +83,645 @@ impl error::ResponseError for LotteryError {
         match *self {
             LotteryError::InvalidParameter => HttpResponse::new(http::StatusCode::BAD_REQUEST),
             LotteryError::NoEventAvailable => HttpResponse::with_body(http::StatusCode::SERVICE_UNAVAILABLE, "No event available on eventbrite"),
+            LotteryError::EventNotFound => HttpResponse::with_body(http::StatusCode::NOT_FOUND, "Event not found in the live events"),
+            LotteryError::DrawNotFound => HttpResponse::new(http::StatusCode::NOT_FOUND),
+            LotteryError::PrizeNotFound => HttpResponse::new(http::StatusCode::NOT_FOUND),
+            LotteryError::PrizeSoldOut => HttpResponse::with_body(http::StatusCode::CONFLICT, "No unit of the prize left"),
+            LotteryError::PrizeAwarded => HttpResponse::with_body(http::StatusCode::CONFLICT, "Prize already awarded"),
+            LotteryError::WinnerNotFound => HttpResponse::new(http::StatusCode::NOT_FOUND),
+            LotteryError::AttendeeNotFound => HttpResponse::new(http::StatusCode::NOT_FOUND),
+            LotteryError::Unauthorized => HttpResponse::new(http::StatusCode::UNAUTHORIZED),
             LotteryError::DrawError { cause: ref e } => HttpResponse::with_body(http::StatusCode::BAD_REQUEST, format!("{}", e)),
+            LotteryError::EventbriteError { cause: ref e } => HttpResponse::with_body(http::StatusCode::BAD_GATEWAY, format!("{}", e)),
             LotteryError::UnexpectedError { cause: ref e } => HttpResponse::with_body(http::StatusCode::INTERNAL_SERVER_ERROR, format!("{}", e))
         }
     }
 }
 
+/// Wrap an error returned by the database actor
+fn db_error(error: error::Error) -> LotteryError {
+    LotteryError::UnexpectedError { cause: failure::err_msg(format!("{}", error)) }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Strategy {
+    Uniform,
+    Weighted,
+}
+
+impl Default for Strategy {
+    fn default() -> Self {
+        Strategy::Uniform
+    }
+}
+
 #[derive(Deserialize)]
 struct WinnerQuery {
-    nb: i8
+    event_id: Option<String>,
+    nb: i8,
+    #[serde(default)]
+    exclude_winners: bool,
+    #[serde(default)]
+    checked_in_only: bool,
+    #[serde(default)]
+    strategy: Strategy,
+}
+
+/// Load the profiles already recorded as winners for the event, if asked to
+fn excluded_winners(state: &WebState, event_id: Option<String>, exclude_winners: bool) -> Box<dyn Future<Item=Vec<Profile>, Error=LotteryError>> {
+    if !exclude_winners {
+        return Box::new(future::ok(vec![]));
+    }
+    let db = state.db.clone();
+    Box::new(state.cache.send(GetEvent { event_id: event_id })
+        .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+        .and_then(|result| result)
+        .and_then(move |event| db.send(GetWinners { event_id: event.id })
+            .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+            .and_then(|result| result.map_err(db_error)))
+        .map(|winners| winners.into_iter()
+            .map(|winner| Profile { id: winner.attendee_id, ..Profile::new(winner.first_name, winner.last_name) })
+            .collect()))
+}
+
+/// Build the weighted strategy from the attendance history, if asked to
+fn weighted_strategy(state: &WebState, strategy: Strategy) -> Box<dyn Future<Item=Option<Weighted>, Error=LotteryError>> {
+    match strategy {
+        Strategy::Uniform => Box::new(future::ok(None)),
+        Strategy::Weighted => Box::new(state.db.send(GetParticipations {})
+            .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+            .and_then(|result| result.map_err(db_error))
+            .map(|participations| Some(Weighted::from_history(&participations))))
+    }
+}
+
+/// Draw attendees of the event, the default one if not provided
+fn draw(state: &WebState, event_id: Option<String>, nb: i8, exclude_winners: bool, checked_in_only: bool, strategy: Strategy) -> Box<dyn Future<Item=DrawResult, Error=LotteryError>> {
+    let cache = state.cache.clone();
+    Box::new(excluded_winners(state, event_id.clone(), exclude_winners)
+        .join(weighted_strategy(state, strategy))
+        .and_then(move |(excluded, weighted)| cache.send(GetAttendees { event_id: event_id, nb: nb, excluded: excluded, weighted: weighted, checked_in_only: checked_in_only })
+            .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+            .and_then(|result| result)))
+}
+
+/// Inputs and result of a draw to store in the draw log
+fn draw_log(nb: i8, draw: &DrawResult) -> CreateDraw {
+    CreateDraw { event_id: draw.event_id.clone(), seed: draw.seed, nb: nb, attendees: draw.attendees.clone(), winners: draw.winners.clone(), weights: draw.weights.clone() }
+}
+
+/// Tell the WebSocket clients about a stored draw
+fn publish_draw(state: &WebState, logged: &Draw, draw: &DrawResult) {
+    state.broadcaster.do_send(Publish(LotteryEvent::Draw { draw_id: logged.id.clone(), event_id: draw.event_id.clone(), winners: draw.winners.clone() }));
+}
+
+/// Draw attendees of the event, the default one if not provided, and store the draw in the draw log
+fn logged_draw(state: &WebState, event_id: Option<String>, nb: i8, exclude_winners: bool, checked_in_only: bool, strategy: Strategy) -> Box<dyn Future<Item=(Draw, DrawResult), Error=LotteryError>> {
+    let db = state.db.clone();
+    let web_state = state.clone();
+    Box::new(draw(state, event_id, nb, exclude_winners, checked_in_only, strategy)
+        .and_then(move |draw| db.send(draw_log(nb, &draw))
+            .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+            .and_then(|result| result.map_err(db_error))
+            .map(move |logged| {
+                publish_draw(&web_state, &logged, &draw);
+                (logged, draw)
+            })))
+}
+
+/// Tell the WebSocket clients about the recorded winners
+fn publish_winners(state: &WebState, winners: &[Winner]) {
+    state.broadcaster.do_send(Publish(LotteryEvent::WinnersRecorded { winners: winners.to_vec() }));
+}
+
+/// Tell if the attendees of the draw are outdated because the last updates from the source failed
+fn freshness_headers(response: &mut HttpResponseBuilder, freshness: Freshness) -> &mut HttpResponseBuilder {
+    response
+        .header("X-Attendees-Stale", freshness.stale.to_string())
+        .header("X-Attendees-Age", freshness.age.as_secs().to_string())
 }
 
 fn winner_handler((state, query): (State<WebState>, Query<WinnerQuery>)) -> FutureResponse<HttpResponse, LotteryError> {
     match query.nb {
         nb if nb < 0 => Box::new(future::err(LotteryError::InvalidParameter)),
-        _ => state.cache.send(GetAttendees { nb: query.nb })
-            .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
-            .and_then(|result| result)
-            .and_then(|res| Ok(HttpResponse::Ok().json(res)))
+        nb => logged_draw(&state, query.event_id.clone(), nb, query.exclude_winners, query.checked_in_only, query.strategy)
+            .and_then(|(logged, draw)| Ok(freshness_headers(HttpResponse::Ok().header("X-Draw-Id", logged.id), draw.freshness).json(draw.winners)))
             .responder()
     }
 }
 
-/// Async request handler
+#[derive(Deserialize)]
+struct RecordDrawQuery {
+    event_id: Option<String>,
+    nb: i8,
+    #[serde(default)]
+    exclude_winners: bool,
+    #[serde(default)]
+    checked_in_only: bool,
+    #[serde(default)]
+    strategy: Strategy,
+    prize: Option<String>,
+    recorded_by: Option<String>,
+}
+
+#[derive(Serialize)]
+struct RecordedDraw {
+    draw_id: String,
+    winners: Vec<Winner>,
+}
+
+/// Draw the winners and store the draw with its winners at once, so a draw cannot be re-rolled without leaving a trace
+fn record_draw_handler((state, query): (State<WebState>, Query<RecordDrawQuery>)) -> FutureResponse<HttpResponse, LotteryError> {
+    let query = query.into_inner();
+    if query.nb < 0 {
+        return Box::new(future::err(LotteryError::InvalidParameter));
+    }
+    let db = state.db.clone();
+    let web_state = state.clone();
+    let (nb, prize, recorded_by) = (query.nb, query.prize, query.recorded_by);
+    draw(&state, query.event_id, nb, query.exclude_winners, query.checked_in_only, query.strategy)
+        .and_then(move |draw| db.send(RecordDraw { draw: draw_log(nb, &draw), prize: prize, recorded_by: recorded_by })
+            .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+            .and_then(|result| result.map_err(db_error))
+            .map(move |(logged, winners)| {
+                publish_draw(&web_state, &logged, &draw);
+                publish_winners(&web_state, &winners);
+                (RecordedDraw { draw_id: logged.id, winners: winners }, draw.freshness)
+            }))
+        .and_then(|(recorded, freshness)| Ok(freshness_headers(HttpResponse::Ok().header("X-Draw-Id", recorded.draw_id.clone()), freshness).json(recorded)))
+        .responder()
+}
+
+#[derive(Serialize)]
+struct DrawVerification {
+    id: String,
+    event_id: String,
+    seed: String,
+    nb: i32,
+    attendees_hash: String,
+    weighted: bool,
+    /// The stored attendees list matches the hash stored with the draw
+    attendees_verified: bool,
+    result_verified: bool,
+    winners: Vec<Profile>,
+}
+
+/// Replay a stored draw from its inputs and compare with the stored result, and check the attendees against their hash
+fn verify_draw(draw: Draw) -> Result<DrawVerification, failure::Error> {
+    let attendees: Vec<Profile> = serde_json::from_str(&draw.attendees)?;
+    let attendees_verified = attendees_hash(&attendees) == draw.attendees_hash;
+    let winners: Vec<Profile> = serde_json::from_str(&draw.result)?;
+    let seed: u64 = draw.seed.parse()?;
+    let weighted = match draw.weights {
+        Some(ref weights) => {
+            let weights: Vec<f64> = serde_json::from_str(weights)?;
+            Some(Weighted::new(attendees.iter().cloned().zip(weights).collect()))
+        }
+        None => None
+    };
+    let strategy: &dyn DrawStrategy = match weighted {
+        Some(ref weighted) => weighted,
+        None => &Uniform
+    };
+    let result_verified = seeded_draw_with(draw.nb as i8, &attendees, strategy, seed)
+        .map(|replayed| replayed.into_iter().eq(winners.iter()))
+        .unwrap_or(false);
+    Ok(DrawVerification {
+        weighted: weighted.is_some(),
+        attendees_verified: attendees_verified,
+        result_verified: result_verified,
+        winners: winners,
+        id: draw.id,
+        event_id: draw.event_id,
+        seed: draw.seed,
+        nb: draw.nb,
+        attendees_hash: draw.attendees_hash,
+    })
+}
+
+fn verify_draw_handler((state, id): (State<WebState>, Path<String>)) -> FutureResponse<HttpResponse, LotteryError> {
+    state.db.send(GetDraw { id: id.into_inner() })
+        .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+        .and_then(|result| result.map_err(db_error))
+        .and_then(|draw| draw.ok_or(LotteryError::DrawNotFound))
+        .and_then(|draw| verify_draw(draw).map_err(|error| LotteryError::UnexpectedError { cause: error }))
+        .and_then(|verification| Ok(HttpResponse::Ok().json(verification)))
+        .responder()
+}
+
+/// Check that the attendee is one of the winners of the draw of the event, when the record refers to a draw
+fn drawn_attendee(state: &WebState, draw_id: Option<String>, event_id: String, profile: Profile) -> Box<dyn Future<Item=(String, Profile), Error=LotteryError>> {
+    let draw_id = match draw_id {
+        Some(draw_id) => draw_id,
+        None => return Box::new(future::ok((event_id, profile)))
+    };
+    Box::new(state.db.send(GetDraw { id: draw_id })
+        .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+        .and_then(|result| result.map_err(db_error))
+        .and_then(|draw| draw.ok_or(LotteryError::DrawNotFound))
+        .and_then(move |draw| {
+            let winners: Vec<Profile> = serde_json::from_str(&draw.result).map_err(|error| LotteryError::UnexpectedError { cause: error.into() })?;
+            if draw.event_id == event_id && winners.iter().any(|winner| winner.same_person(&profile)) {
+                Ok((event_id, profile))
+            } else {
+                Err(LotteryError::InvalidParameter)
+            }
+        }))
+}
+
+/// Record a winner, who must be an attendee of the event
+///
+/// The attendee is found by its eventbrite id when provided, by its names otherwise, and recorded with the names
+/// and the id of the cached attendee. When a draw is given, the attendee must be one of its winners
 fn record_winner_handler(
     (winner, state): (Json<CreateWinner>, State<WebState>),
-) -> FutureResponse<HttpResponse> {
-    state.cache.send(GetEvent{})
+) -> FutureResponse<HttpResponse, LotteryError> {
+    let winner = winner.into_inner();
+    let db = state.db.clone();
+    let web_state = state.clone();
+    let draw_state = state.clone();
+    let draw_id = winner.draw_id.clone();
+    state.cache.send(FindAttendee { event_id: winner.event_id.clone(), attendee_id: winner.attendee_id.clone(), first_name: winner.first_name.clone(), last_name: winner.last_name.clone() })
+        .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+        .and_then(|result| result)
+        .and_then(move |(event_id, profile)| drawn_attendee(&draw_state, draw_id, event_id, profile))
+        .and_then(move |(event_id, profile)| db.send(CreateWinner { event_id: Some(event_id), first_name: profile.first_name, last_name: profile.last_name, attendee_id: profile.id, ..winner })
+            .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+            .and_then(|result| result))
+        .map(move |winner| {
+            publish_winners(&web_state, &[winner.clone()]);
+            HttpResponse::Ok().json(winner)
+        })
+        .responder()
+}
+
+/// Undo the record of a winner
+fn delete_winner_handler((state, id): (State<WebState>, Path<String>)) -> FutureResponse<HttpResponse, LotteryError> {
+    let web_state = state.clone();
+    state.db.send(DeleteWinner { id: id.into_inner() })
+        .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+        .and_then(|result| result.map_err(db_error))
+        .and_then(|deleted| deleted.ok_or(LotteryError::WinnerNotFound))
+        .map(move |winner| {
+            web_state.broadcaster.do_send(Publish(LotteryEvent::WinnerDeleted { winner: winner }));
+            HttpResponse::NoContent().finish()
+        })
+        .responder()
+}
+
+/// Largest page of the winners history
+const MAX_HISTORY_LIMIT: i64 = 500;
+
+fn default_history_limit() -> i64 {
+    50
+}
+
+#[derive(Deserialize)]
+struct WinnersHistoryQuery {
+    event_id: Option<String>,
+    name: Option<String>,
+    #[serde(default = "default_history_limit")]
+    limit: i64,
+    #[serde(default)]
+    offset: i64,
+}
+
+/// Winners recorded for all the events, the total number of matching winners is sent in the `X-Total-Count` header
+fn winners_history_handler((state, query): (State<WebState>, Query<WinnersHistoryQuery>)) -> FutureResponse<HttpResponse, LotteryError> {
+    let query = query.into_inner();
+    if query.limit < 0 || query.offset < 0 {
+        return Box::new(future::err(LotteryError::InvalidParameter));
+    }
+    state.db.send(SearchWinners { event_id: query.event_id, name: query.name, limit: query.limit.min(MAX_HISTORY_LIMIT), offset: query.offset })
+        .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+        .and_then(|result| result.map_err(db_error))
+        .and_then(|page| Ok(HttpResponse::Ok().header("X-Total-Count", page.total.to_string()).json(page.winners)))
+        .responder()
+}
+
+/// Winners recorded for an event, live or not
+fn event_winners_handler((state, event_id): (State<WebState>, Path<String>)) -> FutureResponse<HttpResponse, LotteryError> {
+    state.db.send(GetWinners { event_id: event_id.into_inner() })
+        .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+        .and_then(|result| result.map_err(db_error))
+        .and_then(|winners| Ok(HttpResponse::Ok().json(winners)))
+        .responder()
+}
+
+#[derive(Deserialize)]
+struct PrizesQuery {
+    event_id: Option<String>
+}
+
+fn prizes_handler((state, query): (State<WebState>, Query<PrizesQuery>)) -> FutureResponse<HttpResponse, LotteryError> {
+    state.db.send(GetPrizes { event_id: query.into_inner().event_id })
+        .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+        .and_then(|result| result.map_err(db_error))
+        .and_then(|prizes| Ok(HttpResponse::Ok().json(prizes)))
+        .responder()
+}
+
+fn prize_handler((state, id): (State<WebState>, Path<String>)) -> FutureResponse<HttpResponse, LotteryError> {
+    state.db.send(GetPrize { id: id.into_inner() })
+        .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+        .and_then(|result| result.map_err(db_error))
+        .and_then(|prize| prize.ok_or(LotteryError::PrizeNotFound))
+        .and_then(|prize| Ok(HttpResponse::Ok().json(prize)))
+        .responder()
+}
+
+/// Create a prize, for the default event if no event is provided
+fn create_prize_handler((prize, state): (Json<CreatePrize>, State<WebState>)) -> FutureResponse<HttpResponse, LotteryError> {
+    if prize.quantity < 0 {
+        return Box::new(future::err(LotteryError::InvalidParameter));
+    }
+    let db = state.db.clone();
+    state.cache.send(GetEvent { event_id: None })
+        .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
         .and_then(move |event| {
-            let mut  winner = winner.into_inner();
-            winner.event_id = event.map(|event| event.id).ok();
-            state.db.send(winner)
+            let mut prize = prize.into_inner();
+            prize.event_id = prize.event_id.or(event.map(|event| event.id).ok());
+            db.send(prize)
+                .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+                .and_then(|result| result.map_err(db_error))
         })
-        .from_err()
-        .and_then(|res| match res {
-            Ok(user) => Ok(HttpResponse::Ok().json(user)),
-            Err(_) => Ok(HttpResponse::InternalServerError().into()),
+        .and_then(|prize| Ok(HttpResponse::Ok().json(prize)))
+        .responder()
+}
+
+fn update_prize_handler((prize, state, id): (Json<UpdatePrize>, State<WebState>, Path<String>)) -> FutureResponse<HttpResponse, LotteryError> {
+    if prize.quantity < 0 {
+        return Box::new(future::err(LotteryError::InvalidParameter));
+    }
+    let mut prize = prize.into_inner();
+    prize.id = id.into_inner();
+    state.db.send(prize)
+        .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+        .and_then(|result| result.map_err(db_error))
+        .and_then(|prize| prize.ok_or(LotteryError::PrizeNotFound))
+        .and_then(|prize| Ok(HttpResponse::Ok().json(prize)))
+        .responder()
+}
+
+fn delete_prize_handler((state, id): (State<WebState>, Path<String>)) -> FutureResponse<HttpResponse, LotteryError> {
+    state.db.send(DeletePrize { id: id.into_inner() })
+        .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+        .and_then(|result| result)
+        .map(|_| HttpResponse::NoContent().finish())
+        .responder()
+}
+
+#[derive(Deserialize)]
+struct PrizeDrawQuery {
+    event_id: Option<String>,
+    #[serde(default)]
+    exclude_winners: bool,
+    #[serde(default)]
+    checked_in_only: bool,
+    #[serde(default)]
+    strategy: Strategy,
+}
+
+#[derive(Serialize)]
+struct PrizeAward {
+    prize: Prize,
+    winner: Winner,
+}
+
+/// Draw a winner for every remaining prize of the event and store the draw with its winners together, published once stored
+fn prize_draw_handler((state, query): (State<WebState>, Query<PrizeDrawQuery>)) -> FutureResponse<HttpResponse, LotteryError> {
+    let db = state.db.clone();
+    let web_state = state.clone();
+    let query = query.into_inner();
+    state.cache.send(GetEvent { event_id: query.event_id.clone() })
+        .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+        .and_then(|result| result)
+        .and_then(move |event| db.send(GetAvailablePrizes { event_id: event.id.clone() })
+            .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+            .and_then(|result| result.map_err(db_error))
+            .map(move |prizes| (event, prizes)))
+        .and_then(|(event, prizes)| if prizes.len() > i8::max_value() as usize { Err(LotteryError::InvalidParameter) } else { Ok((event, prizes)) })
+        .and_then(move |(event, prizes)| draw(&web_state, Some(event.id), prizes.len() as i8, query.exclude_winners, query.checked_in_only, query.strategy)
+            .and_then(move |draw| web_state.db.send(AwardPrizes { draw: draw_log(prizes.len() as i8, &draw), prizes: prizes.clone() })
+                .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+                .and_then(|result| result)
+                .map(move |(logged, winners)| {
+                    publish_draw(&web_state, &logged, &draw);
+                    publish_winners(&web_state, &winners);
+                    let awards = prizes.into_iter().zip(winners)
+                        .map(|(prize, winner)| PrizeAward { prize: prize, winner: winner })
+                        .collect::<Vec<PrizeAward>>();
+                    (logged, draw.freshness, awards)
+                })))
+        .and_then(|(logged, freshness, awards)| Ok(freshness_headers(HttpResponse::Ok().header("X-Draw-Id", logged.id), freshness).json(awards)))
+        .responder()
+}
+
+#[derive(Deserialize)]
+struct EventQuery {
+    event_id: Option<String>
+}
+
+fn events_handler(state: State<WebState>) -> FutureResponse<HttpResponse, LotteryError> {
+    state.cache.send(GetEvents {})
+        .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+        .and_then(|events| Ok(HttpResponse::Ok().json(events)))
+        .responder()
+}
+
+/// Listen to the draws, the attendees updates and the recorded winners as JSON text messages
+fn draws_socket_handler(req: &HttpRequest<WebState>) -> Result<HttpResponse, error::Error> {
+    ws::start(req, DrawSocket::new(req.state().broadcaster.clone()))
+}
+
+/// Update the attendees cache now, without waiting for the next scheduled update
+fn refresh_cache_handler(state: State<WebState>) -> FutureResponse<HttpResponse, LotteryError> {
+    state.cache.send(UpdateAttendees {})
+        .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+        .and_then(|result| result.map_err(|_| LotteryError::UnexpectedError { cause: failure::err_msg("Attendees cache update aborted") }))
+        .and_then(|response| Ok(match response {
+            UpdateAttendeesResponse::EventbriteError { .. } => HttpResponse::BadGateway().json(response),
+            UpdateAttendeesResponse::UnexpectedError { .. } => HttpResponse::InternalServerError().json(response),
+            _ => HttpResponse::Ok().json(response)
+        }))
+        .responder()
+}
+
+/// Details of an event, the default one if not provided
+fn event_handler((state, query): (State<WebState>, Query<EventQuery>)) -> FutureResponse<HttpResponse, LotteryError> {
+    state.cache.send(GetEvent { event_id: query.into_inner().event_id })
+        .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+        .and_then(|result| result)
+        .and_then(|event| Ok(HttpResponse::Ok().json(event)))
+        .responder()
+}
+
+/// Number of attendees of the event used for the draws, the default one if not provided
+fn count_attendees_handler((state, query): (State<WebState>, Query<EventQuery>)) -> FutureResponse<HttpResponse, LotteryError> {
+    state.cache.send(CountAttendees { event_id: query.into_inner().event_id })
+        .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+        .and_then(|result| result)
+        .and_then(|count| Ok(HttpResponse::Ok().json(count)))
+        .responder()
+}
+
+/// Draw screen showing the event, drawing and recording the winners with the API
+fn projector_handler(_req: &HttpRequest<WebState>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(PROJECTOR_PAGE)
+}
+
+/// Add an attendee missing from the attendee source to the event, the default one if not provided
+fn add_attendee_handler((profile, state, query): (Json<Profile>, State<WebState>, Query<EventQuery>)) -> FutureResponse<HttpResponse, LotteryError> {
+    state.cache.send(AddAttendee { event_id: query.into_inner().event_id, profile: profile.into_inner() })
+        .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+        .and_then(|result| result)
+        .and_then(|attendee| Ok(HttpResponse::Ok().json(attendee)))
+        .responder()
+}
+
+fn remove_attendee_handler((state, id): (State<WebState>, Path<String>)) -> FutureResponse<HttpResponse, LotteryError> {
+    state.cache.send(RemoveAttendee { id: id.into_inner() })
+        .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+        .and_then(|result| result)
+        .and_then(|_| Ok(HttpResponse::NoContent().finish()))
+        .responder()
+}
+
+#[derive(Deserialize)]
+struct WebhookQuery {
+    secret: Option<String>
+}
+
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum WebhookResponse {
+    /// The changed attendees were applied to the cache of the event
+    Applied { event_id: String, attendees: usize },
+    /// The action is not handled, or the event is not a live event
+    Ignored,
+}
+
+/// Secret of the webhook, from the `X-Webhook-Secret` header or from the `secret` parameter of the url configured on eventbrite
+fn webhook_secret(req: &HttpRequest<WebState>, query: &WebhookQuery) -> Option<String> {
+    req.headers().get("X-Webhook-Secret")
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_owned())
+        .or_else(|| query.secret.clone())
+}
+
+/// Apply the attendees changed on eventbrite to the cache, without waiting for the next update
+///
+/// Only the url of the payload is used, the changed order or attendee is loaded from eventbrite
+fn eventbrite_webhook_handler((req, payload, query): (HttpRequest<WebState>, Json<WebhookPayload>, Query<WebhookQuery>)) -> FutureResponse<HttpResponse, LotteryError> {
+    let state = req.state();
+    let client = match state.eventbrite {
+        Some(ref client) => client.clone(),
+        None => return Box::new(future::ok(HttpResponse::NotFound().finish()))
+    };
+    if let Some(ref expected) = state.webhook_secret {
+        if !webhook_secret(&req, &query).map(|secret| valid_secret(expected, &secret)).unwrap_or(false) {
+            return Box::new(future::err(LotteryError::Unauthorized));
+        }
+    }
+    let resource = match payload.resource(&client.base_url) {
+        Ok(Some(resource)) => resource,
+        Ok(None) => {
+            info!("Ignore eventbrite webhook {}", payload.config.action);
+            return Box::new(future::ok(HttpResponse::Ok().json(WebhookResponse::Ignored)));
+        }
+        Err(e) => {
+            warn!("Reject eventbrite webhook : {}", e);
+            return Box::new(future::err(LotteryError::InvalidParameter));
+        }
+    };
+    let cache = state.cache.clone();
+    let applied: Box<dyn Future<Item=WebhookResponse, Error=LotteryError>> = match resource {
+        WebhookResource::Order { order_id } => Box::new(load_order(&client, &order_id)
+            .map_err(|error| LotteryError::EventbriteError { cause: error })
+            .and_then(move |order| {
+                let applied = WebhookResponse::Applied { event_id: order.event_id.clone(), attendees: order.attendees.len() };
+                cache.send(AddOrderAttendees { event_id: order.event_id, attendees: order.attendees })
+                    .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+                    .and_then(|result| result)
+                    .map(|_| applied)
+            })),
+        WebhookResource::Attendee { event_id, attendee_id } => Box::new(load_attendee(&client, &event_id, &attendee_id)
+            .map_err(|error| LotteryError::EventbriteError { cause: error })
+            .and_then(move |attendee| cache.send(ReplaceAttendee { event_id: event_id.clone(), attendee: attendee })
+                .map_err(|error| LotteryError::UnexpectedError { cause: error.into() })
+                .and_then(|result| result)
+                .map(|_| WebhookResponse::Applied { event_id: event_id, attendees: 1 })))
+    };
+    applied
+        .or_else(|error| match error {
+            LotteryError::EventNotFound => Ok(WebhookResponse::Ignored),
+            error => Err(error)
         })
+        .and_then(|response| Ok(HttpResponse::Ok().json(response)))
         .responder()
 }
 
-fn main() {
-    env_logger::init();
+/// Delays in seconds between the attendees cache updates, from the CACHE_REFRESH_* env vars
+fn refresh_schedule() -> RefreshSchedule {
+    let seconds = |name: &str, default: Duration| env::var(name).ok()
+        .map(|value| value.parse().map(Duration::from_secs).expect(&format!("{} should be a number of seconds", name)))
+        .unwrap_or(default);
+    let default = RefreshSchedule::default();
+    RefreshSchedule {
+        fast: seconds("CACHE_REFRESH_FAST", default.fast),
+        slow: seconds("CACHE_REFRESH_SLOW", default.slow),
+        window: seconds("CACHE_REFRESH_WINDOW", default.window),
+    }
+}
+
+/// Removal of the duplicated attendee records from the DEDUP_ATTENDEES env var, none by default
+fn dedup() -> Dedup {
+    env::var("DEDUP_ATTENDEES").ok()
+        .map(|value| value.parse().unwrap_or_else(|e| panic!("DEDUP_ATTENDEES is invalid : {}", e)))
+        .unwrap_or_default()
+}
+
+/// Eventbrite client, None if the attendees are loaded from the ATTENDEES_FILE
+fn eventbrite_client() -> Option<EventbriteClient> {
+    if env::var("ATTENDEES_FILE").is_ok() {
+        return None;
+    }
     let organizer = env::var("ORGANIZER_TOKEN").expect("ORGANIZER_TOKEN is mandatory");
     let token = env::var("EVENTBRITE_TOKEN").expect("EVENTBRITE_TOKEN is mandatory");
+    let base_url = env::var("EVENTBRITE_BASE_URL").unwrap_or(EVENTBRITE_BASE_URL.to_string());
+    Some(EventbriteClient::new(base_url, organizer, token))
+}
+
+/// Attendees are loaded from the ATTENDEES_FILE if defined, from eventbrite otherwise
+fn attendee_source(eventbrite: &Option<EventbriteClient>) -> Box<dyn AttendeeSource> {
+    match *eventbrite {
+        Some(ref client) => Box::new(client.clone()),
+        None => {
+            let path = env::var("ATTENDEES_FILE").expect("ATTENDEES_FILE is mandatory without eventbrite");
+            info!("Loading attendees from file {}", path);
+            Box::new(FileSource::new(path.into(), env::var("EVENT_ID").ok()).expect("ATTENDEES_FILE should be a .csv or .json file"))
+        }
+    }
+}
+
+fn main() {
+    env_logger::init();
+    let eventbrite = eventbrite_client();
+    let source = attendee_source(&eventbrite);
+    let webhook_secret = env::var("WEBHOOK_SECRET").ok();
+    if eventbrite.is_some() && webhook_secret.is_none() {
+        warn!("WEBHOOK_SECRET is not defined, anyone can call the eventbrite webhook endpoint");
+    }
 
     let database_url = env::var("DATABASE_URL").expect("DATABASE_URL env var is mandatory");
 
     // Database connection init
     let manager = ConnectionManager::<SqliteConnection>::new(database_url);
     let pool = r2d2::Pool::builder()
+        .connection_customizer(Box::new(BusyTimeout { millis: 5000 }))
         .build(manager)
         .expect("Failed to create pool.");
 
@@ -118,17 +736,54 @@ fn main() {
 
     let db_addr = SyncArbiter::start(3, move || DbExecutor(pool.clone()));
 
-    let addr = LotteryCache::default().start();
-    Arbiter::spawn(cache_update_interval(10, addr.clone(), token.clone(), organizer.clone()));
+    let broadcaster = Broadcaster::default().start();
+    let addr = LotteryCache::new(source, db_addr.clone(), dedup(), broadcaster.clone()).start();
+    // Warm the cache with the attendees saved before the restart, then start the updates from the source
+    let warm_addr = addr.clone();
+    let update_addr = addr.clone();
+    Arbiter::spawn(db_addr.send(GetSnapshots {})
+        .then(move |snapshots| {
+            match snapshots {
+                Ok(Ok(snapshots)) => warm_addr.do_send(WarmCache { snapshots: snapshots }),
+                Ok(Err(e)) => error!("Error on loading attendees snapshots : {}", e),
+                Err(e) => error!("Error on sending snapshots message : {:?}", e)
+            };
+            cache_update_loop(refresh_schedule(), update_addr)
+        }));
 
     let http_bind = env::var("HTTP_BIND").unwrap_or("0.0.0.0".to_string());
     let http_port = env::var("HTTP_PORT").unwrap_or("8088".to_string());
     let addr_cloned = addr.clone();
     HttpServer::new(move ||
-        App::with_state(WebState { cache: addr_cloned.clone(), db: db_addr.clone() })
-            .middleware(middleware::Logger::default())
+        App::with_state(WebState { cache: addr_cloned.clone(), db: db_addr.clone(), broadcaster: broadcaster.clone(), eventbrite: eventbrite.clone(), webhook_secret: webhook_secret.clone() })
+            // The secret of the webhook can be in its url
+            .middleware(middleware::Logger::default().exclude("/webhooks/eventbrite"))
             .resource("/winners", |r| r.method(http::Method::GET).with(winner_handler))
-            .resource("/record", |r| r.method(http::Method::POST).with(record_winner_handler)))
+            .resource("/winners/history", |r| r.method(http::Method::GET).with(winners_history_handler))
+            .resource("/record", |r| r.method(http::Method::POST).with(record_winner_handler))
+            .resource("/record/{id}", |r| r.method(http::Method::DELETE).with(delete_winner_handler))
+            .resource("/cache/refresh", |r| r.method(http::Method::POST).with(refresh_cache_handler))
+            .resource("/webhooks/eventbrite", |r| r.method(http::Method::POST).with(eventbrite_webhook_handler))
+            .resource("/event", |r| r.method(http::Method::GET).with(event_handler))
+            .resource("/events", |r| r.method(http::Method::GET).with(events_handler))
+            .resource("/events/{id}/winners", |r| r.method(http::Method::GET).with(event_winners_handler))
+            .resource("/", |r| r.method(http::Method::GET).f(projector_handler))
+            .resource("/attendees", |r| r.method(http::Method::POST).with(add_attendee_handler))
+            .resource("/attendees/count", |r| r.method(http::Method::GET).with(count_attendees_handler))
+            .resource("/attendees/{id}", |r| r.method(http::Method::DELETE).with(remove_attendee_handler))
+            .resource("/ws/draws", |r| r.method(http::Method::GET).f(draws_socket_handler))
+            .resource("/draws", |r| r.method(http::Method::POST).with(record_draw_handler))
+            .resource("/draws/{id}/verify", |r| r.method(http::Method::GET).with(verify_draw_handler))
+            .resource("/prizes", |r| {
+                r.method(http::Method::GET).with(prizes_handler);
+                r.method(http::Method::POST).with(create_prize_handler);
+            })
+            .resource("/prizes/draw", |r| r.method(http::Method::POST).with(prize_draw_handler))
+            .resource("/prizes/{id}", |r| {
+                r.method(http::Method::GET).with(prize_handler);
+                r.method(http::Method::PUT).with(update_prize_handler);
+                r.method(http::Method::DELETE).with(delete_prize_handler);
+            }))
         .bind(format!("{}:{}", http_bind, http_port))
         .unwrap()
         .start();
@@ -3,26 +3,193 @@ use actix::prelude::*;
 use actix_web::*;
 use diesel;
 use diesel::prelude::*;
-use diesel::r2d2::{ConnectionManager, Pool};
+use diesel::r2d2::{self, ConnectionManager, CustomizeConnection, Pool};
 use uuid;
+use serde_json;
+use jug_actix_lottery::eventbrite::model::{Attende, Event, Profile};
+use jug_actix_lottery::lottery;
+use jug_actix_lottery::strategy::Participation;
+use std::collections::{HashMap, HashSet};
+use chrono::{NaiveDateTime, Utc};
 
+use attendees::message::ManualAttendee;
+use errors::LotteryError;
 use super::models;
+use super::prizes;
 use super::schema;
 
 /// This is db executor actor. We are going to run 3 of them in parallel.
 pub struct DbExecutor(pub Pool<ConnectionManager<SqliteConnection>>);
 
+/// Wait for the lock of another executor writing in the database, SQLite fails with `database is locked` otherwise
+#[derive(Debug)]
+pub struct BusyTimeout {
+    pub millis: u32,
+}
+
+impl CustomizeConnection<SqliteConnection, r2d2::Error> for BusyTimeout {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), r2d2::Error> {
+        conn.execute(&format!("PRAGMA busy_timeout = {};", self.millis))
+            .map(|_| ())
+            .map_err(r2d2::Error::QueryError)
+    }
+}
+
 /// This is only message that this actor can handle, but it is easy to extend
 /// number of messages.
 #[derive(Serialize, Deserialize)]
 pub struct CreateWinner {
+    #[serde(default)]
     pub first_name: String,
+    #[serde(default)]
     pub last_name: String,
-    pub event_id: Option<String>
+    pub event_id: Option<String>,
+    #[serde(default)]
+    pub prize_id: Option<String>,
+    /// Id of the draw which selected the winner, sent in the `X-Draw-Id` header of the draws
+    #[serde(default)]
+    pub draw_id: Option<String>,
+    #[serde(default)]
+    pub prize: Option<String>,
+    #[serde(default)]
+    pub recorded_by: Option<String>,
+    /// Eventbrite id of the winner, the names are then read from the attendees of the event
+    #[serde(default)]
+    pub attendee_id: Option<String>
 }
 
 impl Message for CreateWinner {
-    type Result = Result<models::Winner, Error>;
+    type Result = Result<models::Winner, LotteryError>;
+}
+
+/// Load the winners already recorded for an event
+pub struct GetWinners {
+    pub event_id: String
+}
+
+impl Message for GetWinners {
+    type Result = Result<Vec<models::Winner>, Error>;
+}
+
+/// Search the recorded winners, by event and by name, one page at a time
+///
+/// Every word of the name must be part of the first name or the last name, ignoring the case
+pub struct SearchWinners {
+    pub event_id: Option<String>,
+    pub name: Option<String>,
+    pub limit: i64,
+    pub offset: i64
+}
+
+/// Page of winners with the total number of winners matching the search
+pub struct WinnersPage {
+    pub winners: Vec<models::Winner>,
+    pub total: i64
+}
+
+impl Message for SearchWinners {
+    type Result = Result<WinnersPage, Error>;
+}
+
+/// Delete a winner recorded by mistake, return false if the winner does not exist
+pub struct DeleteWinner {
+    pub id: String
+}
+
+impl Message for DeleteWinner {
+    /// The deleted winner, None if there was no winner with the id
+    type Result = Result<Option<models::Winner>, Error>;
+}
+
+/// Store the inputs and the result of a draw so it can be verified afterwards
+pub struct CreateDraw {
+    pub event_id: String,
+    pub seed: u64,
+    pub nb: i8,
+    pub attendees: Vec<Profile>,
+    pub winners: Vec<Profile>,
+    pub weights: Option<Vec<f64>>
+}
+
+impl Message for CreateDraw {
+    type Result = Result<models::Draw, Error>;
+}
+
+/// Store a draw and record all its winners in a single transaction, nothing is stored if one insert fails
+pub struct RecordDraw {
+    pub draw: CreateDraw,
+    pub prize: Option<String>,
+    pub recorded_by: Option<String>
+}
+
+impl Message for RecordDraw {
+    type Result = Result<(models::Draw, Vec<models::Winner>), Error>;
+}
+
+/// Load a stored draw
+pub struct GetDraw {
+    pub id: String
+}
+
+impl Message for GetDraw {
+    type Result = Result<Option<models::Draw>, Error>;
+}
+
+/// Replace the attendees recorded for an event
+pub struct SaveAttendances {
+    pub event_id: String,
+    pub attendees: Vec<Profile>
+}
+
+impl Message for SaveAttendances {
+    type Result = Result<(), Error>;
+}
+
+/// Load the number of events attended and won by every known person
+pub struct GetParticipations {}
+
+impl Message for GetParticipations {
+    type Result = Result<Vec<Participation>, Error>;
+}
+
+/// Attendees of a live event successfully loaded from the attendee source
+pub struct EventSnapshot {
+    /// Position of the event in the live events
+    pub position: i32,
+    pub event: Event,
+    pub attendees: Vec<Attende>,
+    /// UTC time of the load
+    pub loaded_at: NaiveDateTime,
+    /// Attendees added manually to the event
+    pub manual: Vec<ManualAttendee>,
+}
+
+/// Replace the snapshots of the loaded events and order all the snapshots as the live events,
+/// the snapshots of the events no longer live are removed
+pub struct SaveSnapshots {
+    pub live_events: Vec<String>,
+    pub snapshots: Vec<EventSnapshot>,
+}
+
+impl Message for SaveSnapshots {
+    type Result = Result<(), Error>;
+}
+
+/// Replace the manual attendees saved with the snapshot of an event, ignored while the event has no snapshot
+pub struct SaveManualAttendees {
+    pub event_id: String,
+    pub manual: Vec<ManualAttendee>,
+}
+
+impl Message for SaveManualAttendees {
+    type Result = Result<(), Error>;
+}
+
+/// Load the latest snapshots of the live events, in the order of the live events
+pub struct GetSnapshots {}
+
+impl Message for GetSnapshots {
+    type Result = Result<Vec<EventSnapshot>, Error>;
 }
 
 impl Actor for DbExecutor {
@@ -30,31 +197,391 @@ impl Actor for DbExecutor {
 }
 
 impl Handler<CreateWinner> for DbExecutor {
-    type Result = Result<super::models::Winner, Error>;
+    type Result = Result<super::models::Winner, LotteryError>;
 
+    /// The prize is checked in the transaction inserting the winner, so it cannot be won more than its quantity.
+    /// The prize is given either by its `prize_id`, recorded with the name of the prize, or by a free text `prize`
     fn handle(&mut self, msg: CreateWinner, _: &mut Self::Context) -> Self::Result {
         use self::schema::winners::dsl::*;
 
+        if msg.prize_id.is_some() && msg.prize.is_some() {
+            return Err(LotteryError::InvalidParameter);
+        }
         let uuid = format!("{}", uuid::Uuid::new_v4());
-        let new_user = models::NewWinner {
-            id: &uuid,
-            first_name: &msg.first_name,
-            last_name: &msg.last_name,
-            event_id: &msg.event_id.unwrap_or("Unknown".to_owned()),
+        let winner_event = msg.event_id.clone().unwrap_or("Unknown".to_owned());
+
+        let conn: &SqliteConnection = &self.0.get().unwrap();
+
+        conn.transaction::<_, LotteryError, _>(|| {
+            let won = match msg.prize_id {
+                Some(ref awarded_prize) => Some(prizes::check_remaining(conn, awarded_prize, &winner_event)?),
+                None => None
+            };
+            diesel::insert_into(winners)
+                .values(&models::NewWinner {
+                    id: &uuid,
+                    first_name: &msg.first_name,
+                    last_name: &msg.last_name,
+                    event_id: &winner_event,
+                    prize_id: msg.prize_id.as_ref().map(|awarded| awarded.as_str()),
+                    created_at: Utc::now().naive_utc(),
+                    draw_id: msg.draw_id.as_ref().map(|draw| draw.as_str()),
+                    prize: won.as_ref().map(|won| won.name.as_str()).or(msg.prize.as_ref().map(|description| description.as_str())),
+                    recorded_by: msg.recorded_by.as_ref().map(|recorder| recorder.as_str()),
+                    attendee_id: msg.attendee_id.as_ref().map(|attendee| attendee.as_str()),
+                })
+                .execute(conn)?;
+            Ok(())
+        })?;
+
+        Ok(winners.find(&uuid).first::<models::Winner>(conn)?)
+    }
+}
+
+impl Handler<GetWinners> for DbExecutor {
+    type Result = Result<Vec<models::Winner>, Error>;
+
+    fn handle(&mut self, msg: GetWinners, _: &mut Self::Context) -> Self::Result {
+        use self::schema::winners::dsl::*;
+
+        let conn: &SqliteConnection = &self.0.get().unwrap();
+
+        winners
+            .filter(event_id.eq(&msg.event_id))
+            .order((last_name, first_name))
+            .load::<models::Winner>(conn)
+            .map_err(|_| error::ErrorInternalServerError("Error loading winners"))
+    }
+}
+
+/// Pattern matching the values containing the word, the wildcards of the word are escaped with `\`
+fn contains_pattern(word: &str) -> String {
+    format!("%{}%", word.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"))
+}
+
+/// Winners matching the search, the most recent records first
+fn search_winners_query(msg: &SearchWinners) -> schema::winners::BoxedQuery<diesel::sqlite::Sqlite> {
+    use self::schema::winners::dsl::*;
+
+    let mut query = winners.into_boxed();
+    if let Some(ref event) = msg.event_id {
+        query = query.filter(event_id.eq(event.clone()));
+    }
+    for word in msg.name.iter().flat_map(|name| name.split_whitespace()) {
+        let pattern = contains_pattern(word);
+        query = query.filter(first_name.like(pattern.clone()).escape('\\').or(last_name.like(pattern).escape('\\')));
+    }
+    query
+}
+
+impl Handler<SearchWinners> for DbExecutor {
+    type Result = Result<WinnersPage, Error>;
+
+    fn handle(&mut self, msg: SearchWinners, _: &mut Self::Context) -> Self::Result {
+        use self::schema::winners::dsl::*;
+
+        let conn: &SqliteConnection = &self.0.get().unwrap();
+
+        let total = search_winners_query(&msg)
+            .count()
+            .get_result(conn)
+            .map_err(|_| error::ErrorInternalServerError("Error counting winners"))?;
+        let page = search_winners_query(&msg)
+            .order((created_at.desc(), event_id.desc(), last_name, first_name))
+            .limit(msg.limit)
+            .offset(msg.offset)
+            .load::<models::Winner>(conn)
+            .map_err(|_| error::ErrorInternalServerError("Error loading winners"))?;
+
+        Ok(WinnersPage { winners: page, total: total })
+    }
+}
+
+impl Handler<DeleteWinner> for DbExecutor {
+    type Result = Result<Option<models::Winner>, Error>;
+
+    fn handle(&mut self, msg: DeleteWinner, _: &mut Self::Context) -> Self::Result {
+        use self::schema::winners::dsl::*;
+
+        let conn: &SqliteConnection = &self.0.get().unwrap();
+
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            let deleted = winners.find(&msg.id).first::<models::Winner>(conn).optional()?;
+            if deleted.is_some() {
+                diesel::delete(winners.find(&msg.id)).execute(conn)?;
+            }
+            Ok(deleted)
+        }).map_err(|_| error::ErrorInternalServerError("Error deleting winner"))
+    }
+}
+
+impl Handler<RecordDraw> for DbExecutor {
+    type Result = Result<(models::Draw, Vec<models::Winner>), Error>;
+
+    fn handle(&mut self, msg: RecordDraw, _: &mut Self::Context) -> Self::Result {
+        use self::schema::winners::dsl::*;
+
+        let row = DrawRow::new(&msg.draw)?;
+        let conn: &SqliteConnection = &self.0.get().unwrap();
+
+        let ids: Vec<String> = msg.draw.winners.iter().map(|_| format!("{}", uuid::Uuid::new_v4())).collect();
+        let now = Utc::now().naive_utc();
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            row.insert(conn)?;
+            for (profile, uuid) in msg.draw.winners.iter().zip(ids.iter()) {
+                diesel::insert_into(winners)
+                    .values(&models::NewWinner {
+                        id: uuid,
+                        first_name: &profile.first_name,
+                        last_name: &profile.last_name,
+                        event_id: &msg.draw.event_id,
+                        prize_id: None,
+                        created_at: now,
+                        draw_id: Some(&row.id),
+                        prize: msg.prize.as_ref().map(|won| won.as_str()),
+                        recorded_by: msg.recorded_by.as_ref().map(|recorder| recorder.as_str()),
+                        attendee_id: profile.id.as_ref().map(|attendee| attendee.as_str()),
+                    })
+                    .execute(conn)?;
+            }
+            Ok(())
+        }).map_err(|err| { error!("Error recording draw winners : {:?}", err); error::ErrorInternalServerError("Error recording draw winners") })?;
+
+        let mut recorded = winners
+            .filter(id.eq_any(&ids))
+            .load::<models::Winner>(conn)
+            .map_err(|_| error::ErrorInternalServerError("Error loading winners"))?;
+        recorded.sort_by_key(|winner| ids.iter().position(|uuid| uuid == &winner.id));
+        Ok((load_draw(conn, &row.id)?, recorded))
+    }
+}
+
+/// Draw serialized for the draws table
+pub struct DrawRow {
+    pub id: String,
+    event_id: String,
+    seed: String,
+    attendees_hash: String,
+    nb: i32,
+    attendees: String,
+    result: String,
+    weights: Option<String>,
+}
+
+impl DrawRow {
+    pub fn new(msg: &CreateDraw) -> Result<DrawRow, Error> {
+        let weights = match msg.weights {
+            Some(ref weights) => Some(serde_json::to_string(weights)
+                .map_err(|_| error::ErrorInternalServerError("Error serializing weights"))?),
+            None => None
         };
+        Ok(DrawRow {
+            id: format!("{}", uuid::Uuid::new_v4()),
+            event_id: msg.event_id.clone(),
+            seed: format!("{}", msg.seed),
+            attendees_hash: lottery::attendees_hash(&msg.attendees),
+            nb: msg.nb as i32,
+            attendees: serde_json::to_string(&msg.attendees)
+                .map_err(|_| error::ErrorInternalServerError("Error serializing attendees"))?,
+            result: serde_json::to_string(&msg.winners)
+                .map_err(|_| error::ErrorInternalServerError("Error serializing winners"))?,
+            weights: weights,
+        })
+    }
+
+    pub fn insert(&self, conn: &SqliteConnection) -> QueryResult<usize> {
+        diesel::insert_into(schema::draws::table)
+            .values(&models::NewDraw {
+                id: &self.id,
+                event_id: &self.event_id,
+                seed: &self.seed,
+                attendees_hash: &self.attendees_hash,
+                nb: self.nb,
+                attendees: &self.attendees,
+                result: &self.result,
+                weights: self.weights.as_ref().map(|weights| weights.as_str()),
+            })
+            .execute(conn)
+    }
+}
+
+fn load_draw(conn: &SqliteConnection, draw_id: &str) -> Result<models::Draw, Error> {
+    schema::draws::table
+        .find(draw_id)
+        .first::<models::Draw>(conn)
+        .map_err(|_| error::ErrorInternalServerError("Error loading draw"))
+}
+
+impl Handler<CreateDraw> for DbExecutor {
+    type Result = Result<models::Draw, Error>;
+
+    fn handle(&mut self, msg: CreateDraw, _: &mut Self::Context) -> Self::Result {
+        let row = DrawRow::new(&msg)?;
+        let conn: &SqliteConnection = &self.0.get().unwrap();
+
+        row.insert(conn)
+            .map_err(|err| { error!("Error inserting draw : {:?}", err); error::ErrorInternalServerError("Error inserting draw") })?;
+
+        load_draw(conn, &row.id)
+    }
+}
+
+impl Handler<GetDraw> for DbExecutor {
+    type Result = Result<Option<models::Draw>, Error>;
+
+    fn handle(&mut self, msg: GetDraw, _: &mut Self::Context) -> Self::Result {
+        use self::schema::draws::dsl::*;
 
         let conn: &SqliteConnection = &self.0.get().unwrap();
 
-        diesel::insert_into(winners)
-            .values(&new_user)
+        let mut items = draws
+            .filter(id.eq(&msg.id))
+            .load::<models::Draw>(conn)
+            .map_err(|_| error::ErrorInternalServerError("Error loading draw"))?;
+
+        Ok(items.pop())
+    }
+}
+
+impl Handler<SaveAttendances> for DbExecutor {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, msg: SaveAttendances, _: &mut Self::Context) -> Self::Result {
+        use self::schema::attendances::dsl::*;
+
+        let new_attendances: Vec<models::NewAttendance> = msg.attendees.iter()
+            .map(|attendee| models::NewAttendance {
+                id: format!("{}", uuid::Uuid::new_v4()),
+                event_id: &msg.event_id,
+                first_name: &attendee.first_name,
+                last_name: &attendee.last_name,
+            })
+            .collect();
+
+        let conn: &SqliteConnection = &self.0.get().unwrap();
+
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            diesel::delete(attendances.filter(event_id.eq(&msg.event_id))).execute(conn)?;
+            diesel::insert_into(attendances).values(&new_attendances).execute(conn)
+        }).map_err(|err| { error!("Error saving attendances : {:?}", err); error::ErrorInternalServerError("Error saving attendances") })?;
+
+        Ok(())
+    }
+}
+
+impl Handler<GetParticipations> for DbExecutor {
+    type Result = Result<Vec<Participation>, Error>;
+
+    fn handle(&mut self, _msg: GetParticipations, _: &mut Self::Context) -> Self::Result {
+        let conn: &SqliteConnection = &self.0.get().unwrap();
+
+        let attended = schema::attendances::table
+            .select((schema::attendances::event_id, schema::attendances::first_name, schema::attendances::last_name))
+            .load::<(String, String, String)>(conn)
+            .map_err(|_| error::ErrorInternalServerError("Error loading attendances"))?;
+        let won = schema::winners::table
+            .select((schema::winners::event_id, schema::winners::first_name, schema::winners::last_name))
+            .load::<(String, String, String)>(conn)
+            .map_err(|_| error::ErrorInternalServerError("Error loading winners"))?;
+
+        let attended_events = events_by_profile(attended);
+        let won_events = events_by_profile(won);
+        Ok(attended_events.iter()
+            .map(|(profile, events)| Participation {
+                profile: profile.clone(),
+                attended: events.len() as u32,
+                won: won_events.get(profile).map(|events| events.len() as u32).unwrap_or(0),
+            })
+            .collect())
+    }
+}
+
+impl Handler<SaveSnapshots> for DbExecutor {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, msg: SaveSnapshots, _: &mut Self::Context) -> Self::Result {
+        use self::schema::attendee_snapshots::dsl::*;
+
+        let new_snapshots = msg.snapshots.iter()
+            .map(|snapshot| Ok(models::NewAttendeeSnapshot {
+                event_id: &snapshot.event.id,
+                position: snapshot.position,
+                event: serde_json::to_string(&snapshot.event)?,
+                attendees: serde_json::to_string(&snapshot.attendees)?,
+                loaded_at: snapshot.loaded_at,
+                manual: serde_json::to_string(&snapshot.manual)?,
+            }))
+            .collect::<Result<Vec<models::NewAttendeeSnapshot>, serde_json::Error>>()
+            .map_err(|_| error::ErrorInternalServerError("Error serializing attendees snapshot"))?;
+
+        let conn: &SqliteConnection = &self.0.get().unwrap();
+
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            diesel::delete(attendee_snapshots.filter(event_id.ne_all(&msg.live_events))).execute(conn)?;
+            diesel::replace_into(attendee_snapshots).values(&new_snapshots).execute(conn)?;
+            // The snapshots kept for the events failing to load follow the new order too
+            for (live_position, live_event) in msg.live_events.iter().enumerate() {
+                diesel::update(attendee_snapshots.filter(event_id.eq(live_event)))
+                    .set(position.eq(live_position as i32))
+                    .execute(conn)?;
+            }
+            Ok(())
+        }).map_err(|err| { error!("Error saving attendees snapshot : {:?}", err); error::ErrorInternalServerError("Error saving attendees snapshot") })?;
+
+        Ok(())
+    }
+}
+
+impl Handler<SaveManualAttendees> for DbExecutor {
+    type Result = Result<(), Error>;
+
+    fn handle(&mut self, msg: SaveManualAttendees, _: &mut Self::Context) -> Self::Result {
+        use self::schema::attendee_snapshots::dsl::*;
+
+        let saved = serde_json::to_string(&msg.manual)
+            .map_err(|_| error::ErrorInternalServerError("Error serializing manual attendees"))?;
+
+        let conn: &SqliteConnection = &self.0.get().unwrap();
+
+        diesel::update(attendee_snapshots.filter(event_id.eq(&msg.event_id)))
+            .set(manual.eq(saved))
             .execute(conn)
-            .map_err(|err| { eprintln!("{:?}", err); error::ErrorInternalServerError("Error inserting person") })?;
+            .map_err(|err| { error!("Error saving manual attendees : {:?}", err); error::ErrorInternalServerError("Error saving manual attendees") })?;
 
-        let mut items = winners
-            .filter(id.eq(&uuid))
-            .load::<models::Winner>(conn)
-            .map_err(|_| error::ErrorInternalServerError("Error loading person"))?;
+        Ok(())
+    }
+}
+
+impl Handler<GetSnapshots> for DbExecutor {
+    type Result = Result<Vec<EventSnapshot>, Error>;
 
-        Ok(items.pop().unwrap())
+    fn handle(&mut self, _msg: GetSnapshots, _: &mut Self::Context) -> Self::Result {
+        use self::schema::attendee_snapshots::dsl::*;
+
+        let conn: &SqliteConnection = &self.0.get().unwrap();
+
+        attendee_snapshots
+            .select((position, event, attendees, loaded_at, manual))
+            .order(position.asc())
+            .load::<models::AttendeeSnapshot>(conn)
+            .map_err(|_| error::ErrorInternalServerError("Error loading attendees snapshots"))?
+            .into_iter()
+            .map(|snapshot| Ok(EventSnapshot {
+                position: snapshot.position,
+                event: serde_json::from_str(&snapshot.event)?,
+                attendees: serde_json::from_str(&snapshot.attendees)?,
+                loaded_at: snapshot.loaded_at,
+                manual: serde_json::from_str(&snapshot.manual)?,
+            }))
+            .collect::<Result<Vec<EventSnapshot>, serde_json::Error>>()
+            .map_err(|_| error::ErrorInternalServerError("Error reading attendees snapshots"))
     }
 }
+
+/// Group the distinct event ids by profile
+fn events_by_profile(rows: Vec<(String, String, String)>) -> HashMap<Profile, HashSet<String>> {
+    rows.into_iter().fold(HashMap::new(), |mut events, (event, first, last)| {
+        events.entry(Profile::new(first, last)).or_insert_with(HashSet::new).insert(event);
+        events
+    })
+}
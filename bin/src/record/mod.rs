@@ -1,3 +1,4 @@
 pub mod db;
 pub mod models;
+pub mod prizes;
 pub mod schema;
\ No newline at end of file
@@ -1,11 +1,24 @@
-use super::schema::winners;
+use super::schema::{attendances, attendee_snapshots, draws, prizes, winners};
+use chrono::NaiveDateTime;
 
-#[derive(Serialize, Queryable, Debug)]
+#[derive(Serialize, Queryable, Debug, Clone)]
 pub struct Winner {
     pub id: String,
     pub first_name: String,
     pub last_name: String,
-    pub event_id: String
+    pub event_id: String,
+    /// Prize of `/prizes` won, the source of truth of the prize when set
+    pub prize_id: Option<String>,
+    /// UTC time of the record, unknown for the winners recorded before it was stored
+    pub created_at: Option<NaiveDateTime>,
+    /// Draw which selected the winner
+    pub draw_id: Option<String>,
+    /// Name of the prize of `prize_id` when it was won, or description of a prize not managed with `/prizes`
+    pub prize: Option<String>,
+    /// Person who recorded the winner
+    pub recorded_by: Option<String>,
+    /// Eventbrite id of the winner, None for the attendees without id
+    pub attendee_id: Option<String>
 }
 
 #[derive(Insertable)]
@@ -14,5 +27,94 @@ pub struct NewWinner<'a> {
     pub id: &'a str,
     pub first_name: &'a str,
     pub last_name: &'a str,
+    pub event_id: &'a str,
+    pub prize_id: Option<&'a str>,
+    pub created_at: NaiveDateTime,
+    pub draw_id: Option<&'a str>,
+    pub prize: Option<&'a str>,
+    pub recorded_by: Option<&'a str>,
+    pub attendee_id: Option<&'a str>
+}
+
+#[derive(Queryable, Debug)]
+pub struct Draw {
+    pub id: String,
+    pub event_id: String,
+    pub seed: String,
+    pub attendees_hash: String,
+    pub nb: i32,
+    pub attendees: String,
+    pub result: String,
+    pub weights: Option<String>
+}
+
+#[derive(Insertable)]
+#[table_name = "draws"]
+pub struct NewDraw<'a> {
+    pub id: &'a str,
+    pub event_id: &'a str,
+    pub seed: &'a str,
+    pub attendees_hash: &'a str,
+    pub nb: i32,
+    pub attendees: &'a str,
+    pub result: &'a str,
+    pub weights: Option<&'a str>
+}
+
+#[derive(Insertable)]
+#[table_name = "attendances"]
+pub struct NewAttendance<'a> {
+    pub id: String,
+    pub event_id: &'a str,
+    pub first_name: &'a str,
+    pub last_name: &'a str
+}
+
+#[derive(Serialize, Queryable, Debug, Clone)]
+pub struct Prize {
+    pub id: String,
+    pub name: String,
+    pub sponsor: Option<String>,
+    pub quantity: i32,
+    pub event_id: String
+}
+
+#[derive(Insertable)]
+#[table_name = "prizes"]
+pub struct NewPrize<'a> {
+    pub id: &'a str,
+    pub name: &'a str,
+    pub sponsor: Option<&'a str>,
+    pub quantity: i32,
     pub event_id: &'a str
 }
+
+#[derive(AsChangeset)]
+#[table_name = "prizes"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct PrizeChanges<'a> {
+    pub name: &'a str,
+    pub sponsor: Option<&'a str>,
+    pub quantity: i32
+}
+
+/// Snapshot of the attendees of an event, the event id is part of the serialized event
+#[derive(Queryable, Debug)]
+pub struct AttendeeSnapshot {
+    pub position: i32,
+    pub event: String,
+    pub attendees: String,
+    pub loaded_at: NaiveDateTime,
+    pub manual: String
+}
+
+#[derive(Insertable)]
+#[table_name = "attendee_snapshots"]
+pub struct NewAttendeeSnapshot<'a> {
+    pub event_id: &'a str,
+    pub position: i32,
+    pub event: String,
+    pub attendees: String,
+    pub loaded_at: NaiveDateTime,
+    pub manual: String
+}
@@ -0,0 +1,368 @@
+//! Prizes catalogue messages handled by the db executor actor
+use actix::prelude::*;
+use actix_web::*;
+use diesel;
+use diesel::prelude::*;
+use uuid;
+use chrono::Utc;
+use chrono::NaiveDateTime;
+use errors::LotteryError;
+use failure;
+
+use super::db::{CreateDraw, DbExecutor, DrawRow};
+use super::models;
+use super::schema;
+
+#[derive(Serialize, Deserialize)]
+pub struct CreatePrize {
+    pub name: String,
+    pub sponsor: Option<String>,
+    pub quantity: i32,
+    pub event_id: Option<String>
+}
+
+impl Message for CreatePrize {
+    type Result = Result<models::Prize, Error>;
+}
+
+/// Load the prizes, of an event if provided
+pub struct GetPrizes {
+    pub event_id: Option<String>
+}
+
+impl Message for GetPrizes {
+    type Result = Result<Vec<models::Prize>, Error>;
+}
+
+pub struct GetPrize {
+    pub id: String
+}
+
+impl Message for GetPrize {
+    type Result = Result<Option<models::Prize>, Error>;
+}
+
+#[derive(Deserialize)]
+pub struct UpdatePrize {
+    #[serde(skip)]
+    pub id: String,
+    pub name: String,
+    pub sponsor: Option<String>,
+    pub quantity: i32
+}
+
+impl Message for UpdatePrize {
+    type Result = Result<Option<models::Prize>, Error>;
+}
+
+/// Delete a prize never awarded, SQLite does not enforce the references of the winners to their prize
+pub struct DeletePrize {
+    pub id: String
+}
+
+impl Message for DeletePrize {
+    type Result = Result<(), LotteryError>;
+}
+
+/// Load the prizes of an event not yet won, a prize is repeated for each remaining unit
+pub struct GetAvailablePrizes {
+    pub event_id: String
+}
+
+impl Message for GetAvailablePrizes {
+    type Result = Result<Vec<models::Prize>, Error>;
+}
+
+/// Store a draw and record its winners of the prizes in a single transaction
+///
+/// The winners of the draw win the prizes in order. Nothing is stored if a prize does not have enough remaining units
+pub struct AwardPrizes {
+    pub draw: CreateDraw,
+    pub prizes: Vec<models::Prize>
+}
+
+impl Message for AwardPrizes {
+    type Result = Result<(models::Draw, Vec<models::Winner>), LotteryError>;
+}
+
+fn load_prize(conn: &SqliteConnection, prize_id: &str) -> Result<Option<models::Prize>, Error> {
+    use self::schema::prizes::dsl::*;
+
+    let mut items = prizes
+        .filter(id.eq(prize_id))
+        .load::<models::Prize>(conn)
+        .map_err(|_| error::ErrorInternalServerError("Error loading prize"))?;
+
+    Ok(items.pop())
+}
+
+fn awarded_count(conn: &SqliteConnection, awarded_prize: &str) -> Result<i64, diesel::result::Error> {
+    use self::schema::winners::dsl::*;
+
+    winners
+        .filter(prize_id.eq(awarded_prize))
+        .count()
+        .get_result(conn)
+}
+
+/// Load a prize of the event with a unit left, in the transaction recording its winner
+pub fn check_remaining(conn: &SqliteConnection, awarded_prize: &str, winner_event: &str) -> Result<models::Prize, LotteryError> {
+    let prize = schema::prizes::table
+        .find(awarded_prize)
+        .first::<models::Prize>(conn)
+        .optional()?
+        .ok_or(LotteryError::PrizeNotFound)?;
+    if prize.event_id != winner_event {
+        return Err(LotteryError::InvalidParameter);
+    }
+    if awarded_count(conn, &prize.id)? >= prize.quantity as i64 {
+        return Err(LotteryError::PrizeSoldOut);
+    }
+    Ok(prize)
+}
+
+impl Handler<CreatePrize> for DbExecutor {
+    type Result = Result<models::Prize, Error>;
+
+    fn handle(&mut self, msg: CreatePrize, _: &mut Self::Context) -> Self::Result {
+        use self::schema::prizes::dsl::*;
+
+        let uuid = format!("{}", uuid::Uuid::new_v4());
+        let new_prize = models::NewPrize {
+            id: &uuid,
+            name: &msg.name,
+            sponsor: msg.sponsor.as_ref().map(|value| value.as_str()),
+            quantity: msg.quantity,
+            event_id: &msg.event_id.unwrap_or("Unknown".to_owned()),
+        };
+
+        let conn: &SqliteConnection = &self.0.get().unwrap();
+
+        diesel::insert_into(prizes)
+            .values(&new_prize)
+            .execute(conn)
+            .map_err(|err| { error!("Error inserting prize : {:?}", err); error::ErrorInternalServerError("Error inserting prize") })?;
+
+        load_prize(conn, &uuid)?.ok_or(error::ErrorInternalServerError("Error loading prize"))
+    }
+}
+
+impl Handler<GetPrizes> for DbExecutor {
+    type Result = Result<Vec<models::Prize>, Error>;
+
+    fn handle(&mut self, msg: GetPrizes, _: &mut Self::Context) -> Self::Result {
+        use self::schema::prizes::dsl::*;
+
+        let conn: &SqliteConnection = &self.0.get().unwrap();
+
+        let query = match msg.event_id {
+            Some(ref event) => prizes.filter(event_id.eq(event)).order(name).into_boxed(),
+            None => prizes.order(name).into_boxed()
+        };
+        query
+            .load::<models::Prize>(conn)
+            .map_err(|_| error::ErrorInternalServerError("Error loading prizes"))
+    }
+}
+
+impl Handler<GetPrize> for DbExecutor {
+    type Result = Result<Option<models::Prize>, Error>;
+
+    fn handle(&mut self, msg: GetPrize, _: &mut Self::Context) -> Self::Result {
+        let conn: &SqliteConnection = &self.0.get().unwrap();
+
+        load_prize(conn, &msg.id)
+    }
+}
+
+impl Handler<UpdatePrize> for DbExecutor {
+    type Result = Result<Option<models::Prize>, Error>;
+
+    fn handle(&mut self, msg: UpdatePrize, _: &mut Self::Context) -> Self::Result {
+        use self::schema::prizes::dsl::*;
+
+        let changes = models::PrizeChanges {
+            name: &msg.name,
+            sponsor: msg.sponsor.as_ref().map(|value| value.as_str()),
+            quantity: msg.quantity,
+        };
+
+        let conn: &SqliteConnection = &self.0.get().unwrap();
+
+        diesel::update(prizes.filter(id.eq(&msg.id)))
+            .set(&changes)
+            .execute(conn)
+            .map_err(|err| { error!("Error updating prize : {:?}", err); error::ErrorInternalServerError("Error updating prize") })?;
+
+        load_prize(conn, &msg.id)
+    }
+}
+
+impl Handler<DeletePrize> for DbExecutor {
+    type Result = Result<(), LotteryError>;
+
+    /// The awarded prizes are kept, the winners reference them
+    fn handle(&mut self, msg: DeletePrize, _: &mut Self::Context) -> Self::Result {
+        use self::schema::prizes::dsl::*;
+
+        let conn: &SqliteConnection = &self.0.get().unwrap();
+
+        conn.transaction::<_, LotteryError, _>(|| {
+            if awarded_count(conn, &msg.id)? > 0 {
+                return Err(LotteryError::PrizeAwarded);
+            }
+            match diesel::delete(prizes.filter(id.eq(&msg.id))).execute(conn)? {
+                0 => Err(LotteryError::PrizeNotFound),
+                _ => Ok(())
+            }
+        })
+    }
+}
+
+impl Handler<GetAvailablePrizes> for DbExecutor {
+    type Result = Result<Vec<models::Prize>, Error>;
+
+    fn handle(&mut self, msg: GetAvailablePrizes, _: &mut Self::Context) -> Self::Result {
+        use self::schema::prizes::dsl::*;
+
+        let conn: &SqliteConnection = &self.0.get().unwrap();
+
+        let event_prizes = prizes
+            .filter(event_id.eq(&msg.event_id))
+            .order(name)
+            .load::<models::Prize>(conn)
+            .map_err(|_| error::ErrorInternalServerError("Error loading prizes"))?;
+
+        let mut available = vec![];
+        for prize in event_prizes {
+            let awarded = awarded_count(conn, &prize.id)
+                .map_err(|_| error::ErrorInternalServerError("Error loading prizes"))?;
+            let remaining = (prize.quantity as i64 - awarded).max(0);
+            for _ in 0..remaining {
+                available.push(prize.clone());
+            }
+        }
+        Ok(available)
+    }
+}
+
+/// Insert the draw and the winners of the prizes with the given ids, nothing is inserted if a prize does not have enough units left
+fn award_prizes(conn: &SqliteConnection, msg: &AwardPrizes, row: &DrawRow, ids: &[String], now: NaiveDateTime) -> Result<(), LotteryError> {
+    use self::schema::winners::dsl::*;
+
+    conn.transaction::<_, LotteryError, _>(|| {
+        row.insert(conn)?;
+        for ((won, profile), uuid) in msg.prizes.iter().zip(msg.draw.winners.iter()).zip(ids.iter()) {
+            check_remaining(conn, &won.id, &msg.draw.event_id)?;
+            diesel::insert_into(winners)
+                .values(&models::NewWinner {
+                    id: uuid,
+                    first_name: &profile.first_name,
+                    last_name: &profile.last_name,
+                    event_id: &msg.draw.event_id,
+                    prize_id: Some(&won.id),
+                    created_at: now,
+                    draw_id: Some(&row.id),
+                    prize: Some(&won.name),
+                    recorded_by: None,
+                    attendee_id: profile.id.as_ref().map(|attendee| attendee.as_str()),
+                })
+                .execute(conn)?;
+        }
+        Ok(())
+    })
+}
+
+impl Handler<AwardPrizes> for DbExecutor {
+    type Result = Result<(models::Draw, Vec<models::Winner>), LotteryError>;
+
+    fn handle(&mut self, msg: AwardPrizes, _: &mut Self::Context) -> Self::Result {
+        use self::schema::winners::dsl::*;
+
+        let row = DrawRow::new(&msg.draw).map_err(|error| LotteryError::UnexpectedError { cause: failure::err_msg(format!("{}", error)) })?;
+        let conn: &SqliteConnection = &self.0.get().unwrap();
+
+        let ids: Vec<String> = msg.prizes.iter().map(|_| format!("{}", uuid::Uuid::new_v4())).collect();
+        award_prizes(conn, &msg, &row, &ids, Utc::now().naive_utc())?;
+
+        let mut awarded = winners
+            .filter(id.eq_any(&ids))
+            .load::<models::Winner>(conn)?;
+        awarded.sort_by_key(|winner| ids.iter().position(|uuid| uuid == &winner.id));
+        Ok((schema::draws::table.find(&row.id).first::<models::Draw>(conn)?, awarded))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_migrations;
+    use jug_actix_lottery::eventbrite::model::Profile;
+
+    fn connection() -> SqliteConnection {
+        let conn = SqliteConnection::establish(":memory:").unwrap();
+        embedded_migrations::run(&conn).unwrap();
+        conn
+    }
+
+    fn insert_prize(conn: &SqliteConnection, prize_id: &str, quantity: i32) -> models::Prize {
+        diesel::insert_into(schema::prizes::table)
+            .values(&models::NewPrize { id: prize_id, name: prize_id, sponsor: None, quantity: quantity, event_id: "52097259305" })
+            .execute(conn)
+            .unwrap();
+        schema::prizes::table.find(prize_id).first(conn).unwrap()
+    }
+
+    fn award(conn: &SqliteConnection, awards: Vec<(models::Prize, Profile)>) -> Result<(), LotteryError> {
+        let (prizes, winners): (Vec<models::Prize>, Vec<Profile>) = awards.into_iter().unzip();
+        let draw = CreateDraw { event_id: "52097259305".to_string(), seed: 42, nb: winners.len() as i8, attendees: winners.clone(), winners: winners, weights: None };
+        let msg = AwardPrizes { draw: draw, prizes: prizes };
+        let row = DrawRow::new(&msg.draw).unwrap();
+        let ids: Vec<String> = msg.prizes.iter().map(|_| format!("{}", uuid::Uuid::new_v4())).collect();
+        award_prizes(conn, &msg, &row, &ids, Utc::now().naive_utc())
+    }
+
+    fn winners_count(conn: &SqliteConnection) -> i64 {
+        schema::winners::table.count().get_result(conn).unwrap()
+    }
+
+    fn draws_count(conn: &SqliteConnection) -> i64 {
+        schema::draws::table.count().get_result(conn).unwrap()
+    }
+
+    #[test]
+    fn test_award_prizes_is_all_or_nothing() {
+        let conn = connection();
+        let book = insert_prize(&conn, "book", 1);
+        let licence = insert_prize(&conn, "licence", 1);
+
+        // The second unit of the book is rejected, the licence is not awarded either
+        let result = award(&conn, vec![
+            (book.clone(), Profile::new("Francois", "Teychene")),
+            (licence.clone(), Profile::new("Fabien", "Bernard")),
+            (book.clone(), Profile::new("Jean-Luc", "Racine")),
+        ]);
+        assert!(matches!(result, Err(LotteryError::PrizeSoldOut)));
+        assert_eq!(winners_count(&conn), 0);
+        assert_eq!(draws_count(&conn), 0);
+
+        award(&conn, vec![(book.clone(), Profile::new("Francois", "Teychene")), (licence.clone(), Profile::new("Fabien", "Bernard"))]).unwrap();
+        assert_eq!(winners_count(&conn), 2);
+        assert_eq!(draws_count(&conn), 1);
+        assert!(matches!(award(&conn, vec![(book, Profile::new("Jean-Luc", "Racine"))]), Err(LotteryError::PrizeSoldOut)));
+        assert_eq!(winners_count(&conn), 2);
+        assert_eq!(draws_count(&conn), 1);
+    }
+
+    #[test]
+    fn test_check_remaining() {
+        let conn = connection();
+        let book = insert_prize(&conn, "book", 1);
+        assert_eq!(check_remaining(&conn, "book", "52097259305").unwrap().name, "book");
+        assert!(matches!(check_remaining(&conn, "book", "42"), Err(LotteryError::InvalidParameter)));
+        assert!(matches!(check_remaining(&conn, "licence", "52097259305"), Err(LotteryError::PrizeNotFound)));
+
+        award(&conn, vec![(book, Profile::new("Francois", "Teychene"))]).unwrap();
+        assert!(matches!(check_remaining(&conn, "book", "52097259305"), Err(LotteryError::PrizeSoldOut)));
+    }
+}
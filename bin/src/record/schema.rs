@@ -1,8 +1,68 @@
+table! {
+    attendances (id) {
+        id -> Text,
+        event_id -> Text,
+        first_name -> Text,
+        last_name -> Text,
+    }
+}
+
+table! {
+    attendee_snapshots (event_id) {
+        event_id -> Text,
+        position -> Integer,
+        event -> Text,
+        attendees -> Text,
+        loaded_at -> Timestamp,
+        manual -> Text,
+    }
+}
+
+table! {
+    draws (id) {
+        id -> Text,
+        event_id -> Text,
+        seed -> Text,
+        attendees_hash -> Text,
+        nb -> Integer,
+        attendees -> Text,
+        result -> Text,
+        weights -> Nullable<Text>,
+    }
+}
+
+table! {
+    prizes (id) {
+        id -> Text,
+        name -> Text,
+        sponsor -> Nullable<Text>,
+        quantity -> Integer,
+        event_id -> Text,
+    }
+}
+
 table! {
     winners (id) {
         id -> Text,
         first_name -> Text,
         last_name -> Text,
         event_id -> Text,
+        prize_id -> Nullable<Text>,
+        created_at -> Nullable<Timestamp>,
+        draw_id -> Nullable<Text>,
+        prize -> Nullable<Text>,
+        recorded_by -> Nullable<Text>,
+        attendee_id -> Nullable<Text>,
     }
 }
+
+joinable!(winners -> draws (draw_id));
+joinable!(winners -> prizes (prize_id));
+
+allow_tables_to_appear_in_same_query!(
+    attendee_snapshots,
+    attendances,
+    draws,
+    prizes,
+    winners,
+);
@@ -0,0 +1,621 @@
+//! End to end tests of the lottery binary running against the fake eventbrite
+
+extern crate actix;
+extern crate actix_web;
+extern crate diesel;
+extern crate fake_eventbrite;
+extern crate reqwest;
+#[macro_use]
+extern crate serde_json;
+
+use actix::{Recipient, System};
+use actix_web::server::StopServer;
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use fake_eventbrite::FakeEventbrite;
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const ORGANIZER: &str = "jug";
+const TOKEN: &str = "fake-token";
+const EVENT_ID: &str = "52097259305";
+/// Attendees of the fixtures who can win, without the duplicated record and the cancelled one
+const NB_ATTENDEES: usize = 4;
+const WEBHOOK_SECRET: &str = "webhook-secret";
+
+/// Running lottery binary, killed with its database on drop
+struct Lottery {
+    process: Child,
+    url: String,
+    port: u16,
+    eventbrite_url: String,
+    eventbrite: Recipient<StopServer>,
+    database: PathBuf,
+}
+
+impl Lottery {
+    /// Connection to the database of the lottery, waiting for the lock while the lottery writes
+    fn connection(&self) -> SqliteConnection {
+        let conn = SqliteConnection::establish(self.database.to_str().unwrap()).unwrap();
+        conn.execute("PRAGMA busy_timeout = 5000;").unwrap();
+        conn
+    }
+
+    /// Stop the fake eventbrite, the next updates of the lottery fail
+    fn stop_eventbrite(&self) {
+        self.eventbrite.do_send(StopServer { graceful: false }).unwrap();
+        let addr = self.eventbrite_url.trim_start_matches("http://").to_owned();
+        wait_until(|| TcpStream::connect(&addr).is_err());
+    }
+
+    /// Kill the lottery and start it again on the same database
+    fn restart(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        self.process = spawn_lottery(&self.eventbrite_url, self.port, &self.database);
+        wait_ready(&self.url);
+    }
+}
+
+impl Drop for Lottery {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        let _ = fs::remove_file(&self.database);
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+fn wait_until<F: FnMut() -> bool>(mut ready: F) {
+    let start = Instant::now();
+    while !ready() {
+        assert!(start.elapsed() < Duration::from_secs(30), "Timeout while waiting for the servers");
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Start the fake eventbrite in its own actix system, return its base url and the recipient stopping it
+fn start_fake_eventbrite() -> (String, Recipient<StopServer>) {
+    let addr = format!("127.0.0.1:{}", free_port());
+    let bind = addr.clone();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let system = System::new("fake-eventbrite");
+        sender.send(FakeEventbrite::from_fixtures(ORGANIZER, TOKEN).start(&bind).unwrap()).unwrap();
+        system.run();
+    });
+    let server = receiver.recv().unwrap();
+    wait_until(|| TcpStream::connect(&addr).is_ok());
+    (format!("http://{}", addr), server)
+}
+
+/// The lottery binary is built by cargo next to the tests directory
+fn lottery_binary() -> PathBuf {
+    let mut path = env::current_exe().unwrap();
+    path.pop();
+    if path.ends_with("deps") {
+        path.pop();
+    }
+    path.join(format!("lottery-jug-actix{}", env::consts::EXE_SUFFIX))
+}
+
+fn spawn_lottery(eventbrite_url: &str, port: u16, database: &PathBuf) -> Child {
+    Command::new(lottery_binary())
+        .env("EVENTBRITE_BASE_URL", eventbrite_url)
+        .env("ORGANIZER_TOKEN", ORGANIZER)
+        .env("EVENTBRITE_TOKEN", TOKEN)
+        .env("WEBHOOK_SECRET", WEBHOOK_SECRET)
+        .env("DEDUP_ATTENDEES", "email")
+        .env("DATABASE_URL", database)
+        .env("HTTP_BIND", "127.0.0.1")
+        .env("HTTP_PORT", port.to_string())
+        .env_remove("ATTENDEES_FILE")
+        .stdout(Stdio::null())
+        .spawn()
+        .expect("The lottery binary should be built")
+}
+
+/// Wait for the attendees to be loaded
+fn wait_ready(url: &str) {
+    wait_until(|| reqwest::get(&format!("{}/winners?nb=0", url))
+        .map(|response| response.status().is_success())
+        .unwrap_or(false));
+}
+
+/// Start the lottery on the fake eventbrite and wait for the attendees to be loaded
+fn start_lottery() -> Lottery {
+    let (eventbrite_url, eventbrite) = start_fake_eventbrite();
+    let port = free_port();
+    let database = env::temp_dir().join(format!("lottery-e2e-{}.db", port));
+    let process = spawn_lottery(&eventbrite_url, port, &database);
+    let lottery = Lottery { process: process, url: format!("http://127.0.0.1:{}", port), port: port, eventbrite_url: eventbrite_url, eventbrite: eventbrite, database: database };
+    wait_ready(&lottery.url);
+    lottery
+}
+
+/// WebSocket client reading the text messages sent by the lottery, just enough of the protocol for the tests
+struct Socket {
+    stream: TcpStream,
+}
+
+impl Socket {
+    fn connect(url: &str, path: &str) -> Socket {
+        let host = url.trim_start_matches("http://");
+        let mut stream = TcpStream::connect(host).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n", path, host).unwrap();
+        let mut response = vec![];
+        let mut byte = [0u8];
+        while !response.ends_with(b"\r\n\r\n") {
+            stream.read_exact(&mut byte).unwrap();
+            response.push(byte[0]);
+        }
+        assert!(String::from_utf8(response).unwrap().starts_with("HTTP/1.1 101"));
+        stream.set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+        Socket { stream: stream }
+    }
+
+    /// Next text message, None if nothing is received before the read timeout. The server frames are not masked
+    fn next_text(&mut self) -> Option<String> {
+        loop {
+            let mut header = [0u8; 2];
+            if self.stream.read_exact(&mut header).is_err() {
+                return None;
+            }
+            let len = match header[1] & 0x7f {
+                126 => {
+                    let mut len = [0u8; 2];
+                    self.stream.read_exact(&mut len).unwrap();
+                    ((len[0] as usize) << 8) | len[1] as usize
+                }
+                127 => {
+                    let mut len = [0u8; 8];
+                    self.stream.read_exact(&mut len).unwrap();
+                    len.iter().fold(0, |len, &byte| (len << 8) | byte as usize)
+                }
+                len => len as usize
+            };
+            let mut payload = vec![0u8; len];
+            self.stream.read_exact(&mut payload).unwrap();
+            // Skip the pings of the heartbeat
+            if header[0] & 0x0f == 0x1 {
+                return Some(String::from_utf8(payload).unwrap());
+            }
+        }
+    }
+
+    /// Next lottery event of the type, skipping the other events
+    fn next_event(&mut self, event_type: &str, timeout: Duration) -> Option<Value> {
+        let start = Instant::now();
+        while start.elapsed() < timeout {
+            if let Some(text) = self.next_text() {
+                let event: Value = serde_json::from_str(&text).unwrap();
+                if event["type"] == event_type {
+                    return Some(event);
+                }
+            }
+        }
+        None
+    }
+}
+
+fn names(profiles: &Value) -> Vec<String> {
+    profiles.as_array().unwrap().iter()
+        .map(|profile| format!("{} {}", profile["first_name"].as_str().unwrap(), profile["last_name"].as_str().unwrap()))
+        .collect()
+}
+
+#[test]
+fn test_winners() {
+    let lottery = start_lottery();
+
+    let mut response = reqwest::get(&format!("{}/winners?nb=3", lottery.url)).unwrap();
+    assert!(response.status().is_success());
+    assert!(response.headers().contains_key("X-Draw-Id"));
+    let winners: Value = response.json().unwrap();
+    let mut winners = names(&winners);
+    assert_eq!(winners.len(), 3);
+    winners.sort();
+    winners.dedup();
+    assert_eq!(winners.len(), 3);
+
+    let response = reqwest::get(&format!("{}/winners?nb={}", lottery.url, NB_ATTENDEES + 1)).unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    // The cancelled attendee is never drawn
+    let mut response = reqwest::get(&format!("{}/winners?nb={}", lottery.url, NB_ATTENDEES)).unwrap();
+    let winners: Value = response.json().unwrap();
+    assert!(!names(&winners).contains(&"Hugo Lassiege".to_string()));
+
+    let mut response = reqwest::get(&format!("{}/winners?nb=3&checked_in_only=true", lottery.url)).unwrap();
+    let winners: Value = response.json().unwrap();
+    let mut winners = names(&winners);
+    winners.sort();
+    assert_eq!(winners, vec!["Fabien Bernard", "Francois Teychene", "Renard Chenapan"]);
+}
+
+#[test]
+fn test_record() {
+    let lottery = start_lottery();
+    let client = reqwest::Client::new();
+
+    let mut response = client.post(&format!("{}/record", lottery.url))
+        .json(&json!({"first_name": "Francois", "last_name": "Teychene"}))
+        .send()
+        .unwrap();
+    assert!(response.status().is_success());
+    let winner: Value = response.json().unwrap();
+    assert_eq!(winner["event_id"], EVENT_ID);
+    assert_eq!(winner["first_name"], "Francois");
+    assert_eq!(winner["attendee_id"], "1060946531");
+    assert!(winner["created_at"].is_string());
+    assert_eq!(winner["draw_id"], Value::Null);
+
+    let mut response = reqwest::get(&format!("{}/winners?nb={}&exclude_winners=true", lottery.url, NB_ATTENDEES - 1)).unwrap();
+    assert!(response.status().is_success());
+    let winners: Value = response.json().unwrap();
+    assert!(!names(&winners).contains(&"Francois Teychene".to_string()));
+
+    let response = reqwest::get(&format!("{}/winners?nb={}&exclude_winners=true", lottery.url, NB_ATTENDEES)).unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    // Only the attendees of a live event can be recorded
+    let record = |winner: Value| client.post(&format!("{}/record", lottery.url)).json(&winner).send().unwrap().status();
+    assert_eq!(record(json!({"first_name": "Ada", "last_name": "Lovelace"})), reqwest::StatusCode::NOT_FOUND);
+    assert_eq!(record(json!({"attendee_id": "1060946587"})), reqwest::StatusCode::NOT_FOUND);
+    assert_eq!(record(json!({"first_name": "Francois", "last_name": "Teychene", "event_id": "42"})), reqwest::StatusCode::NOT_FOUND);
+    assert_eq!(record(json!({"first_name": "Hugo", "last_name": "Lassiege"})), reqwest::StatusCode::NOT_FOUND);
+    assert_eq!(record(json!({"attendee_id": "1060946535"})), reqwest::StatusCode::NOT_FOUND);
+
+    // Metadata of a recorded draw
+    let mut response = reqwest::get(&format!("{}/winners?nb=1", lottery.url)).unwrap();
+    let draw_id = response.headers()["X-Draw-Id"].to_str().unwrap().to_string();
+    let drawn: Value = response.json().unwrap();
+    assert!(drawn[0]["id"].is_string());
+    // Only the winners of the draw can be recorded with it
+    let not_drawn = if drawn[0]["id"] == "1060946532" { "1060946533" } else { "1060946532" };
+    assert_eq!(record(json!({"attendee_id": not_drawn, "draw_id": draw_id})), reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(record(json!({"attendee_id": drawn[0]["id"], "draw_id": "unknown"})), reqwest::StatusCode::NOT_FOUND);
+    let mut response = client.post(&format!("{}/record", lottery.url))
+        .json(&json!({"attendee_id": drawn[0]["id"], "draw_id": draw_id, "prize": "Rust in Action", "recorded_by": "orga"}))
+        .send()
+        .unwrap();
+    let winner: Value = response.json().unwrap();
+    assert_eq!(winner["first_name"], drawn[0]["first_name"]);
+    assert_eq!(winner["attendee_id"], drawn[0]["id"]);
+    assert_eq!(winner["draw_id"], json!(draw_id));
+    assert_eq!(winner["prize"], "Rust in Action");
+    assert_eq!(winner["recorded_by"], "orga");
+}
+
+#[test]
+fn test_record_draw() {
+    let lottery = start_lottery();
+    let client = reqwest::Client::new();
+
+    let mut response = client.post(&format!("{}/draws?nb=2&checked_in_only=true&prize=Sticker&recorded_by=orga", lottery.url)).send().unwrap();
+    assert!(response.status().is_success());
+    let draw_id = response.headers()["X-Draw-Id"].to_str().unwrap().to_string();
+    let draw: Value = response.json().unwrap();
+    assert_eq!(draw["draw_id"], json!(draw_id));
+    let winners = &draw["winners"];
+    assert_eq!(names(winners).len(), 2);
+    for winner in winners.as_array().unwrap() {
+        assert_eq!(winner["event_id"], EVENT_ID);
+        assert_eq!(winner["draw_id"], json!(draw_id));
+        assert_eq!(winner["prize"], "Sticker");
+        assert_eq!(winner["recorded_by"], "orga");
+        assert!(winner["attendee_id"].is_string());
+    }
+
+    let mut response = reqwest::get(&format!("{}/events/{}/winners", lottery.url, EVENT_ID)).unwrap();
+    let mut recorded = names(&response.json().unwrap());
+    let mut drawn = names(winners);
+    recorded.sort();
+    drawn.sort();
+    assert_eq!(recorded, drawn);
+
+    let verify = || -> Value { reqwest::get(&format!("{}/draws/{}/verify", lottery.url, draw_id)).unwrap().json().unwrap() };
+    assert_eq!(verify()["result_verified"], true);
+    assert_eq!(verify()["attendees_verified"], true);
+
+    // A tampered result does not match the replay of the draw
+    let conn = lottery.connection();
+    diesel::sql_query("UPDATE draws SET result = '[{\"first_name\": \"Hugo\", \"last_name\": \"Lassiege\"}]' WHERE id = ?")
+        .bind::<Text, _>(&draw_id)
+        .execute(&conn)
+        .unwrap();
+    assert_eq!(verify()["result_verified"], false);
+
+    // The replay of tampered attendees and result matches, but not the hash of the attendees
+    diesel::sql_query("UPDATE draws SET nb = 1, weights = NULL, attendees = result WHERE id = ?")
+        .bind::<Text, _>(&draw_id)
+        .execute(&conn)
+        .unwrap();
+    assert_eq!(verify()["result_verified"], true);
+    assert_eq!(verify()["attendees_verified"], false);
+
+    // Only one checked in attendee was not drawn, nothing is recorded when the draw fails
+    let response = client.post(&format!("{}/draws?nb=2&checked_in_only=true&exclude_winners=true", lottery.url)).send().unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let response = client.post(&format!("{}/draws?nb=-1", lottery.url)).send().unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let mut response = reqwest::get(&format!("{}/events/{}/winners", lottery.url, EVENT_ID)).unwrap();
+    assert_eq!(names(&response.json().unwrap()).len(), 2);
+}
+
+#[test]
+fn test_draws_socket() {
+    let lottery = start_lottery();
+    let client = reqwest::Client::new();
+    let mut socket = Socket::connect(&lottery.url, "/ws/draws");
+
+    // The socket subscribes once connected, draw until it receives the draws
+    let mut draw_ids = vec![];
+    let mut draw = None;
+    while draw.is_none() {
+        assert!(draw_ids.len() < 20, "The socket should receive the draws");
+        let response = reqwest::get(&format!("{}/winners?nb=2", lottery.url)).unwrap();
+        draw_ids.push(json!(response.headers()["X-Draw-Id"].to_str().unwrap()));
+        draw = socket.next_event("draw", Duration::from_millis(500));
+    }
+    let draw = draw.unwrap();
+    assert!(draw_ids.contains(&draw["draw_id"]));
+    assert_eq!(draw["event_id"], EVENT_ID);
+    assert_eq!(names(&draw["winners"]).len(), 2);
+
+    client.post(&format!("{}/record", lottery.url)).json(&json!({"first_name": "Francois", "last_name": "Teychene"})).send().unwrap();
+    let recorded = socket.next_event("winners_recorded", Duration::from_secs(5)).unwrap();
+    assert_eq!(names(&recorded["winners"]), vec!["Francois Teychene"]);
+    assert_eq!(recorded["winners"][0]["attendee_id"], "1060946531");
+
+    let response = client.delete(&format!("{}/record/{}", lottery.url, recorded["winners"][0]["id"].as_str().unwrap())).send().unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+    let deleted = socket.next_event("winner_deleted", Duration::from_secs(5)).unwrap();
+    assert_eq!(deleted["winner"], recorded["winners"][0]);
+
+    client.post(&format!("{}/cache/refresh", lottery.url)).send().unwrap();
+    let refreshed = socket.next_event("cache_refreshed", Duration::from_secs(5)).unwrap();
+    assert_eq!(refreshed["events"], json!([{"event_id": EVENT_ID, "attendees": NB_ATTENDEES, "stale": false}]));
+
+    let added: Value = client.post(&format!("{}/attendees", lottery.url)).json(&json!({"first_name": "Walk", "last_name": "In"})).send().unwrap().json().unwrap();
+    let refreshed = socket.next_event("cache_refreshed", Duration::from_secs(5)).unwrap();
+    assert_eq!(refreshed["events"], json!([{"event_id": EVENT_ID, "attendees": NB_ATTENDEES + 1, "stale": false}]));
+    client.delete(&format!("{}/attendees/{}", lottery.url, added["id"].as_str().unwrap())).send().unwrap();
+    let refreshed = socket.next_event("cache_refreshed", Duration::from_secs(5)).unwrap();
+    assert_eq!(refreshed["events"], json!([{"event_id": EVENT_ID, "attendees": NB_ATTENDEES, "stale": false}]));
+}
+
+#[test]
+fn test_projector() {
+    let lottery = start_lottery();
+
+    let mut response = reqwest::get(&format!("{}/", lottery.url)).unwrap();
+    assert!(response.status().is_success());
+    assert!(response.headers()["Content-Type"].to_str().unwrap().starts_with("text/html"));
+    let page = response.text().unwrap();
+    assert!(page.contains("/winners?"));
+    assert!(page.contains("/ws/draws"));
+    assert!(page.contains("winner_deleted"));
+
+    let mut response = reqwest::get(&format!("{}/attendees/count", lottery.url)).unwrap();
+    assert!(response.status().is_success());
+    let count: Value = response.json().unwrap();
+    assert_eq!(count, json!({"event_id": EVENT_ID, "attendees": NB_ATTENDEES, "stale": false}));
+    let response = reqwest::get(&format!("{}/attendees/count?event_id=42", lottery.url)).unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn test_prizes() {
+    let lottery = start_lottery();
+    let client = reqwest::Client::new();
+    let prize_url = |id: &Value| format!("{}/prizes/{}", lottery.url, id.as_str().unwrap());
+
+    let mut response = client.post(&format!("{}/prizes", lottery.url)).json(&json!({"name": "Rust in Action", "sponsor": "Manning", "quantity": 1})).send().unwrap();
+    assert!(response.status().is_success());
+    let book: Value = response.json().unwrap();
+    assert_eq!(book["event_id"], EVENT_ID);
+    let licence: Value = client.post(&format!("{}/prizes", lottery.url)).json(&json!({"name": "IntelliJ licence", "quantity": 2})).send().unwrap().json().unwrap();
+    let response = client.post(&format!("{}/prizes", lottery.url)).json(&json!({"name": "Sticker", "quantity": -1})).send().unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let prizes: Value = reqwest::get(&format!("{}/prizes?event_id={}", lottery.url, EVENT_ID)).unwrap().json().unwrap();
+    assert_eq!(prizes.as_array().unwrap().iter().map(|prize| prize["name"].as_str().unwrap()).collect::<Vec<_>>(), vec!["IntelliJ licence", "Rust in Action"]);
+    let loaded: Value = reqwest::get(&prize_url(&book["id"])).unwrap().json().unwrap();
+    assert_eq!(loaded, book);
+    assert_eq!(reqwest::get(&format!("{}/prizes/unknown", lottery.url)).unwrap().status(), reqwest::StatusCode::NOT_FOUND);
+
+    let mut response = client.put(&prize_url(&licence["id"])).json(&json!({"name": "IntelliJ licence", "sponsor": "JetBrains", "quantity": 1})).send().unwrap();
+    assert!(response.status().is_success());
+    let licence: Value = response.json().unwrap();
+    assert_eq!(licence["sponsor"], "JetBrains");
+    assert_eq!(licence["quantity"], 1);
+    let response = client.put(&format!("{}/prizes/unknown", lottery.url)).json(&json!({"name": "Sticker", "quantity": 1})).send().unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+    // A prize recorded with a winner cannot be won more than its quantity
+    let record = |first_name: &str, last_name: &str, prize_id: &Value| client.post(&format!("{}/record", lottery.url))
+        .json(&json!({"first_name": first_name, "last_name": last_name, "prize_id": prize_id}))
+        .send()
+        .unwrap()
+        .status();
+    let response = client.post(&format!("{}/record", lottery.url))
+        .json(&json!({"first_name": "Francois", "last_name": "Teychene", "prize_id": &book["id"], "prize": "Sticker"}))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    assert!(record("Francois", "Teychene", &book["id"]).is_success());
+    let winners: Value = reqwest::get(&format!("{}/events/{}/winners", lottery.url, EVENT_ID)).unwrap().json().unwrap();
+    assert_eq!(winners[0]["prize_id"], book["id"]);
+    assert_eq!(winners[0]["prize"], "Rust in Action");
+    assert_eq!(record("Fabien", "Bernard", &book["id"]), reqwest::StatusCode::CONFLICT);
+    assert_eq!(record("Fabien", "Bernard", &json!("unknown")), reqwest::StatusCode::NOT_FOUND);
+
+    // Only the remaining licence is drawn
+    let mut response = client.post(&format!("{}/prizes/draw?exclude_winners=true", lottery.url)).send().unwrap();
+    assert!(response.status().is_success());
+    let awards: Value = response.json().unwrap();
+    assert_eq!(awards.as_array().unwrap().len(), 1);
+    assert_eq!(awards[0]["prize"]["id"], licence["id"]);
+    assert_eq!(awards[0]["winner"]["prize_id"], licence["id"]);
+    let mut response = client.post(&format!("{}/prizes/draw", lottery.url)).send().unwrap();
+    assert_eq!(response.json::<Value>().unwrap(), json!([]));
+
+    // The awarded prizes are kept for their winners
+    assert_eq!(client.delete(&prize_url(&book["id"])).send().unwrap().status(), reqwest::StatusCode::CONFLICT);
+    let sticker: Value = client.post(&format!("{}/prizes", lottery.url)).json(&json!({"name": "Sticker", "quantity": 10})).send().unwrap().json().unwrap();
+    assert_eq!(client.delete(&prize_url(&sticker["id"])).send().unwrap().status(), reqwest::StatusCode::NO_CONTENT);
+    assert_eq!(client.delete(&prize_url(&sticker["id"])).send().unwrap().status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn test_cache_refresh() {
+    let lottery = start_lottery();
+
+    let mut response = reqwest::Client::new().post(&format!("{}/cache/refresh", lottery.url)).send().unwrap();
+    assert!(response.status().is_success());
+    let refresh: Value = response.json().unwrap();
+    // The second ticket of Francois Teychene
+    assert_eq!(refresh, json!({"status": "updated", "removed_duplicates": 1}));
+
+    // The attendance of the cancelled attendee is not recorded for the weighted draws
+    let conn = lottery.connection();
+    let attendances = || -> Vec<String> {
+        diesel::select(sql::<Text>("first_name FROM attendances ORDER BY first_name")).load::<String>(&conn).unwrap()
+    };
+    wait_until(|| attendances().len() == NB_ATTENDEES);
+    assert_eq!(attendances(), vec!["Fabien", "Francois", "Jean-Luc", "Renard"]);
+}
+
+#[test]
+fn test_restart_from_snapshots() {
+    let mut lottery = start_lottery();
+
+    let response = reqwest::get(&format!("{}/winners?nb=1", lottery.url)).unwrap();
+    assert_eq!(response.headers().get("X-Attendees-Stale").unwrap(), "false");
+    let conn = lottery.connection();
+    wait_until(|| diesel::select(sql::<Text>("event_id FROM attendee_snapshots")).load::<String>(&conn).unwrap() == vec![EVENT_ID.to_string()]);
+    let added: Value = reqwest::Client::new().post(&format!("{}/attendees", lottery.url)).json(&json!({"first_name": "Walk", "last_name": "In"})).send().unwrap().json().unwrap();
+    wait_until(|| diesel::select(sql::<Text>("manual FROM attendee_snapshots")).load::<String>(&conn).unwrap() != vec!["[]".to_string()]);
+
+    // Eventbrite is down on restart, the draws use the saved attendees and the manual ones
+    lottery.stop_eventbrite();
+    lottery.restart();
+    let mut response = reqwest::get(&format!("{}/winners?nb={}", lottery.url, NB_ATTENDEES + 1)).unwrap();
+    assert!(response.status().is_success());
+    assert_eq!(response.headers().get("X-Attendees-Stale").unwrap(), "true");
+    let winners: Value = response.json().unwrap();
+    let mut winners = names(&winners);
+    winners.sort();
+    assert_eq!(winners, vec!["Fabien Bernard", "Francois Teychene", "Jean-Luc Racine", "Renard Chenapan", "Walk In"]);
+
+    let count: Value = reqwest::get(&format!("{}/attendees/count", lottery.url)).unwrap().json().unwrap();
+    assert_eq!(count, json!({"event_id": EVENT_ID, "attendees": NB_ATTENDEES + 1, "stale": true}));
+
+    // The manual attendee can still be removed after the restart
+    let response = reqwest::Client::new().delete(&format!("{}/attendees/{}", lottery.url, added["id"].as_str().unwrap())).send().unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+}
+
+#[test]
+fn test_eventbrite_webhook() {
+    let lottery = start_lottery();
+    let client = reqwest::Client::new();
+    let webhook = |secret: &str, action: &str, api_url: &str| client.post(&format!("{}/webhooks/eventbrite?secret={}", lottery.url, secret))
+        .json(&json!({"config": {"action": action, "webhook_id": "1092315"}, "api_url": api_url}))
+        .send()
+        .unwrap();
+
+    let order_url = format!("{}/v3/orders/853311187/", lottery.eventbrite_url);
+    assert_eq!(webhook("wrong-secret", "order.placed", &order_url).status(), reqwest::StatusCode::UNAUTHORIZED);
+    let response = client.post(&format!("{}/webhooks/eventbrite", lottery.url))
+        .json(&json!({"config": {"action": "order.placed", "webhook_id": "1092315"}, "api_url": &order_url}))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let response = client.post(&format!("{}/webhooks/eventbrite", lottery.url))
+        .header("X-Webhook-Secret", "wrong-secret")
+        .json(&json!({"config": {"action": "test", "webhook_id": "1092315"}, "api_url": format!("{}/v3/users/me/", lottery.eventbrite_url)}))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(webhook(WEBHOOK_SECRET, "order.placed", "https://attacker.example.com/v3/orders/853311187/").status(), reqwest::StatusCode::BAD_REQUEST);
+    let response = reqwest::get(&format!("{}/winners?nb={}", lottery.url, NB_ATTENDEES + 1)).unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    // The attendee of the new order can win without waiting for the next update
+    let mut response = webhook(WEBHOOK_SECRET, "order.placed", &order_url);
+    assert!(response.status().is_success());
+    let applied: Value = response.json().unwrap();
+    assert_eq!(applied, json!({"status": "applied", "event_id": EVENT_ID, "attendees": 1}));
+    let mut response = reqwest::get(&format!("{}/winners?nb={}", lottery.url, NB_ATTENDEES + 1)).unwrap();
+    assert!(response.status().is_success());
+    let winners: Value = response.json().unwrap();
+    assert!(names(&winners).contains(&"Ada Lovelace".to_string()));
+
+    let attendee_url = format!("{}/v3/events/{}/attendees/1060946533/", lottery.eventbrite_url, EVENT_ID);
+    let mut response = webhook(WEBHOOK_SECRET, "attendee.checked_in", &attendee_url);
+    assert!(response.status().is_success());
+    let applied: Value = response.json().unwrap();
+    assert_eq!(applied, json!({"status": "applied", "event_id": EVENT_ID, "attendees": 1}));
+
+    // The secret can also be sent in a header
+    let mut response = client.post(&format!("{}/webhooks/eventbrite", lottery.url))
+        .header("X-Webhook-Secret", WEBHOOK_SECRET)
+        .json(&json!({"config": {"action": "test", "webhook_id": "1092315"}, "api_url": format!("{}/v3/users/me/", lottery.eventbrite_url)}))
+        .send()
+        .unwrap();
+    assert!(response.status().is_success());
+    let ignored: Value = response.json().unwrap();
+    assert_eq!(ignored, json!({"status": "ignored"}));
+}
+
+#[test]
+fn test_winners_history() {
+    let lottery = start_lottery();
+    let client = reqwest::Client::new();
+    let record = |winner: Value| -> Value {
+        client.post(&format!("{}/record", lottery.url)).json(&winner).send().unwrap().json().unwrap()
+    };
+    let francois = record(json!({"first_name": "Francois", "last_name": "Teychene"}));
+    record(json!({"first_name": "Fabien", "last_name": "Bernard"}));
+    record(json!({"attendee_id": "1060946533"}));
+    let history = |query: &str| -> (Vec<String>, String) {
+        let mut response = reqwest::get(&format!("{}/winners/history{}", lottery.url, query)).unwrap();
+        assert!(response.status().is_success());
+        let total = response.headers()["X-Total-Count"].to_str().unwrap().to_string();
+        let winners: Value = response.json().unwrap();
+        (names(&winners), total)
+    };
+
+    assert_eq!(history(""), (vec!["Jean-Luc Racine".to_string(), "Fabien Bernard".to_string(), "Francois Teychene".to_string()], "3".to_string()));
+    assert_eq!(history("?event_id=42"), (vec![], "0".to_string()));
+    assert_eq!(history("?name=FRAN"), (vec!["Francois Teychene".to_string()], "1".to_string()));
+    assert_eq!(history("?name=ber%20fab"), (vec!["Fabien Bernard".to_string()], "1".to_string()));
+    assert_eq!(history("?name=%25"), (vec![], "0".to_string()));
+    assert_eq!(history("?limit=1&offset=1"), (vec!["Fabien Bernard".to_string()], "3".to_string()));
+    let response = reqwest::get(&format!("{}/winners/history?limit=-1", lottery.url)).unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let mut response = reqwest::get(&format!("{}/events/{}/winners", lottery.url, EVENT_ID)).unwrap();
+    let winners: Value = response.json().unwrap();
+    assert_eq!(names(&winners), vec!["Fabien Bernard", "Jean-Luc Racine", "Francois Teychene"]);
+
+    // Undo a record
+    let delete = |id: &str| client.delete(&format!("{}/record/{}", lottery.url, id)).send().unwrap().status();
+    assert_eq!(delete(francois["id"].as_str().unwrap()), reqwest::StatusCode::NO_CONTENT);
+    assert_eq!(delete(francois["id"].as_str().unwrap()), reqwest::StatusCode::NOT_FOUND);
+    assert_eq!(history(&format!("?event_id={}", EVENT_ID)), (vec!["Jean-Luc Racine".to_string(), "Fabien Bernard".to_string()], "2".to_string()));
+}
@@ -0,0 +1,210 @@
+//! Stand-in for the eventbrite API serving fixtures, to run the lottery without eventbrite
+//!
+//! Only the endpoints used by the lottery are implemented : the live events of an organization, the attendees of an event,
+//! a single attendee and an order, the last two being loaded by the lottery on webhooks.
+
+extern crate actix;
+extern crate actix_web;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
+
+use actix::Recipient;
+use actix_web::{server, App, HttpRequest, HttpResponse, Path, Query, http};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io;
+
+const EVENTS_FIXTURE: &str = include_str!("../fixtures/events.json");
+const ATTENDEES_FIXTURE: &str = include_str!("../fixtures/attendees.json");
+const ORDERS_FIXTURE: &str = include_str!("../fixtures/orders.json");
+
+/// Organization, token and data served by the fake eventbrite
+#[derive(Clone, Debug)]
+pub struct FakeEventbrite {
+    pub organizer: String,
+    pub token: String,
+    /// Live events of the organization, in the order returned by the API
+    pub events: Vec<Value>,
+    /// Attendees by event id
+    pub attendees: HashMap<String, Vec<Value>>,
+    /// Orders by order id, with their attendees. Their attendees are not listed with the attendees of the event,
+    /// like orders placed after the attendees were loaded
+    pub orders: HashMap<String, Value>,
+    /// Number of attendees on each page
+    pub page_size: usize,
+}
+
+#[derive(Deserialize)]
+struct PageQuery {
+    page: Option<usize>,
+    continuation: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct OrderQuery {
+    expand: Option<String>,
+}
+
+/// Continuation token of a page, the requested page is taken from the token when provided
+fn continuation(page_number: usize) -> String {
+    format!("page-{}", page_number)
+}
+
+fn continuation_page(token: &str) -> Option<usize> {
+    token.trim_start_matches("page-").parse().ok()
+}
+
+impl FakeEventbrite {
+    /// Fake eventbrite serving the fixtures of the `fixtures` directory, the attendees are split on several pages
+    pub fn from_fixtures(organizer: &str, token: &str) -> Self {
+        FakeEventbrite {
+            organizer: organizer.to_owned(),
+            token: token.to_owned(),
+            events: serde_json::from_str(EVENTS_FIXTURE).expect("events fixture should be a JSON array"),
+            attendees: serde_json::from_str(ATTENDEES_FIXTURE).expect("attendees fixture should be a JSON object"),
+            orders: serde_json::from_str(ORDERS_FIXTURE).expect("orders fixture should be a JSON object"),
+            page_size: 2,
+        }
+    }
+
+    /// Start the HTTP server, must be called in a running actix system. The returned recipient stops the server
+    pub fn start(self, addr: &str) -> io::Result<Recipient<server::StopServer>> {
+        let server = server::new(move || app(self.clone()))
+            .workers(1)
+            .bind(addr)?
+            .start();
+        Ok(server.recipient())
+    }
+
+    /// Only the `Authorization: Bearer` header is accepted, a token in the query string is ignored
+    fn authorized(&self, req: &HttpRequest<FakeEventbrite>) -> bool {
+        req.headers().get(http::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value == format!("Bearer {}", self.token))
+            .unwrap_or(false)
+    }
+}
+
+fn app(fake: FakeEventbrite) -> App<FakeEventbrite> {
+    App::with_state(fake)
+        .resource("/v3/organizations/{organizer}/events/", |r| r.method(http::Method::GET).with(events_handler))
+        .resource("/v3/events/{event_id}/attendees/", |r| r.method(http::Method::GET).with(attendees_handler))
+        .resource("/v3/events/{event_id}/attendees/{attendee_id}/", |r| r.method(http::Method::GET).with(attendee_handler))
+        .resource("/v3/orders/{order_id}/", |r| r.method(http::Method::GET).with(order_handler))
+}
+
+fn api_error(status: http::StatusCode, error: &str, description: &str) -> HttpResponse {
+    HttpResponse::build(status).json(json!({
+        "status_code": status.as_u16(),
+        "error": error,
+        "error_description": description
+    }))
+}
+
+fn unauthorized() -> HttpResponse {
+    api_error(http::StatusCode::UNAUTHORIZED, "INVALID_AUTH", "The OAuth token you provided was invalid.")
+}
+
+fn not_found() -> HttpResponse {
+    api_error(http::StatusCode::NOT_FOUND, "NOT_FOUND", "The path you requested does not exist.")
+}
+
+fn events_handler((req, organizer): (HttpRequest<FakeEventbrite>, Path<String>)) -> HttpResponse {
+    let fake = req.state();
+    if !fake.authorized(&req) {
+        return unauthorized();
+    }
+    if *organizer != fake.organizer {
+        return not_found();
+    }
+    HttpResponse::Ok().json(json!({
+        "pagination": {
+            "object_count": fake.events.len(),
+            "page_number": 1,
+            "page_size": fake.events.len(),
+            "page_count": 1,
+            "has_more_items": false
+        },
+        "events": fake.events
+    }))
+}
+
+/// Attendees of the requested page, the page 0 is served as the first page like eventbrite does
+///
+/// A continuation token is provided while there are more pages, it takes precedence over the page number
+fn attendees_handler((req, event_id, query): (HttpRequest<FakeEventbrite>, Path<String>, Query<PageQuery>)) -> HttpResponse {
+    let fake = req.state();
+    if !fake.authorized(&req) {
+        return unauthorized();
+    }
+    let attendees = match fake.attendees.get(&*event_id) {
+        Some(attendees) => attendees,
+        None => return not_found()
+    };
+    let page_size = fake.page_size.max(1);
+    let page_count = attendees.len().div_ceil(page_size).max(1);
+    let page_number = match query.continuation {
+        Some(ref token) => match continuation_page(token) {
+            Some(page_number) => page_number,
+            None => return api_error(http::StatusCode::BAD_REQUEST, "INVALID_CONTINUATION_TOKEN", "The continuation token you provided is invalid.")
+        },
+        None => query.page.unwrap_or(1).max(1)
+    };
+    if page_number > page_count {
+        return api_error(http::StatusCode::BAD_REQUEST, "BAD_PAGE", "The page number you provided is out of range.");
+    }
+    let page: Vec<&Value> = attendees.iter().skip((page_number - 1) * page_size).take(page_size).collect();
+    let mut pagination = json!({
+        "object_count": attendees.len(),
+        "page_number": page_number,
+        "page_size": page_size,
+        "page_count": page_count,
+        "has_more_items": page_number < page_count
+    });
+    if page_number < page_count {
+        pagination["continuation"] = json!(continuation(page_number + 1));
+    }
+    HttpResponse::Ok().json(json!({
+        "pagination": pagination,
+        "attendees": page
+    }))
+}
+
+/// Attendee of an event, listed with the attendees of the event or in one of its orders
+fn attendee_handler((req, path): (HttpRequest<FakeEventbrite>, Path<(String, String)>)) -> HttpResponse {
+    let fake = req.state();
+    if !fake.authorized(&req) {
+        return unauthorized();
+    }
+    let (ref event_id, ref attendee_id) = *path;
+    let listed = fake.attendees.get(event_id).into_iter().flat_map(|attendees| attendees.iter());
+    let ordered = fake.orders.values()
+        .filter(|order| order["event_id"] == *event_id)
+        .flat_map(|order| order["attendees"].as_array().into_iter().flat_map(|attendees| attendees.iter()));
+    match listed.chain(ordered).find(|attendee| attendee["id"] == *attendee_id) {
+        Some(attendee) => HttpResponse::Ok().json(attendee),
+        None => not_found()
+    }
+}
+
+/// Order with its attendees, they are only provided when expanded like eventbrite does
+fn order_handler((req, order_id, query): (HttpRequest<FakeEventbrite>, Path<String>, Query<OrderQuery>)) -> HttpResponse {
+    let fake = req.state();
+    if !fake.authorized(&req) {
+        return unauthorized();
+    }
+    let mut order = match fake.orders.get(&*order_id) {
+        Some(order) => order.clone(),
+        None => return not_found()
+    };
+    let expanded = query.expand.as_ref().map(|expand| expand.split(',').any(|field| field == "attendees")).unwrap_or(false);
+    if !expanded {
+        if let Some(order) = order.as_object_mut() {
+            order.remove("attendees");
+        }
+    }
+    HttpResponse::Ok().json(order)
+}
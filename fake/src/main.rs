@@ -0,0 +1,19 @@
+extern crate actix;
+extern crate fake_eventbrite;
+
+use actix::System;
+use fake_eventbrite::FakeEventbrite;
+use std::env;
+
+fn main() {
+    let organizer = env::var("ORGANIZER_TOKEN").unwrap_or("jug".to_string());
+    let token = env::var("EVENTBRITE_TOKEN").unwrap_or("fake-token".to_string());
+    let http_port = env::var("HTTP_PORT").unwrap_or("8089".to_string());
+
+    let system = System::new("fake-eventbrite");
+    FakeEventbrite::from_fixtures(&organizer, &token)
+        .start(&format!("127.0.0.1:{}", http_port))
+        .expect("Fake eventbrite should bind its port");
+    println!("Fake eventbrite for organizer {} listening on http://127.0.0.1:{}", organizer, http_port);
+    system.run();
+}
@@ -0,0 +1,158 @@
+use eventbrite::model::Attende;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+#[derive(Debug, Fail, PartialEq)]
+#[fail(display = "unknown deduplication {}, expected none, attendee_id, email or name", value)]
+pub struct UnknownDedup {
+    value: String
+}
+
+/// Identity of the attendees used to remove the duplicated records, like the several tickets bought by one person
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dedup {
+    /// Keep every record
+    None,
+    /// Same eventbrite attendee id, the attendees without id are kept
+    AttendeeId,
+    /// Same email ignoring the case, the attendees without email are kept
+    Email,
+    /// Same first name and last name, ignoring the case, the spaces and the hyphens
+    Name,
+}
+
+impl Default for Dedup {
+    fn default() -> Self {
+        Dedup::None
+    }
+}
+
+impl FromStr for Dedup {
+    type Err = UnknownDedup;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "none" => Ok(Dedup::None),
+            "attendee_id" => Ok(Dedup::AttendeeId),
+            "email" => Ok(Dedup::Email),
+            "name" => Ok(Dedup::Name),
+            _ => Err(UnknownDedup { value: value.to_owned() })
+        }
+    }
+}
+
+/// Lower case words of a name, `Jean-Luc  RACINE` and `jean luc racine` are the same name
+fn normalize(name: &str) -> String {
+    name.split(|c: char| c.is_whitespace() || c == '-')
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+impl Dedup {
+    /// Identity of the attendee, None if the attendee cannot be compared
+    fn key(&self, attendee: &Attende) -> Option<String> {
+        match *self {
+            Dedup::None => None,
+            Dedup::AttendeeId => attendee.profile.id.clone(),
+            Dedup::Email => attendee.email.as_ref().map(|email| email.trim().to_lowercase()).filter(|email| !email.is_empty()),
+            Dedup::Name => Some(format!("{}\u{1f}{}", normalize(&attendee.profile.first_name), normalize(&attendee.profile.last_name)))
+        }
+    }
+
+    /// Keep one record per attendee, return the kept attendees and the number of removed records
+    ///
+    /// The attendees keep the position of their first record, the first present record is kept so a checked in person
+    /// stays checked in whatever record was scanned at the entrance
+    pub fn apply(&self, attendees: Vec<Attende>) -> (Vec<Attende>, usize) {
+        let total = attendees.len();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        let mut kept: Vec<Attende> = Vec::with_capacity(total);
+        for attendee in attendees {
+            match self.key(&attendee) {
+                Some(key) => match positions.get(&key) {
+                    Some(&position) => if !kept[position].is_present() && attendee.is_present() {
+                        kept[position] = attendee;
+                    },
+                    None => {
+                        positions.insert(key, kept.len());
+                        kept.push(attendee);
+                    }
+                },
+                None => kept.push(attendee)
+            }
+        }
+        let removed = total - kept.len();
+        (kept, removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eventbrite::model::Profile;
+
+    fn attendee(id: Option<&str>, first_name: &str, last_name: &str, email: Option<&str>) -> Attende {
+        let profile = Profile { id: id.map(|id| id.to_string()), ..Profile::new(first_name.to_string(), last_name.to_string()) };
+        Attende { email: email.map(|email| email.to_string()), ..Attende::registered(profile) }
+    }
+
+    fn names(attendees: &[Attende]) -> Vec<String> {
+        attendees.iter().map(|attendee| format!("{} {}", attendee.profile.first_name, attendee.profile.last_name)).collect()
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("none".parse(), Ok(Dedup::None));
+        assert_eq!("attendee_id".parse(), Ok(Dedup::AttendeeId));
+        assert_eq!(" Email".parse(), Ok(Dedup::Email));
+        assert_eq!("name".parse(), Ok(Dedup::Name));
+        assert!("phone".parse::<Dedup>().is_err());
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("Jean-Luc"), "jean luc");
+        assert_eq!(normalize("  jean   LUC "), "jean luc");
+        assert_eq!(normalize("François"), "françois");
+    }
+
+    #[test]
+    fn test_dedup() {
+        let attendees = vec![
+            attendee(Some("1"), "Francois", "Teychene", Some("francois@example.com")),
+            attendee(Some("2"), "Fabien", "Bernard", Some("Francois@Example.com")),
+            attendee(Some("1"), "Francois", "Teychene", Some("francois@example.com")),
+            attendee(Some("3"), "Jean-Luc", "Racine", None),
+            attendee(Some("4"), "jean luc", "RACINE", None),
+            attendee(None, "Renard", "Chenapan", None),
+            attendee(None, "Renard", "Chenapan", None),
+        ];
+
+        let (kept, removed) = Dedup::None.apply(attendees.clone());
+        assert_eq!((kept, removed), (attendees.clone(), 0));
+
+        let (kept, removed) = Dedup::AttendeeId.apply(attendees.clone());
+        assert_eq!(names(&kept), vec!["Francois Teychene", "Fabien Bernard", "Jean-Luc Racine", "jean luc RACINE", "Renard Chenapan", "Renard Chenapan"]);
+        assert_eq!(removed, 1);
+
+        let (kept, removed) = Dedup::Email.apply(attendees.clone());
+        assert_eq!(names(&kept), vec!["Francois Teychene", "Jean-Luc Racine", "jean luc RACINE", "Renard Chenapan", "Renard Chenapan"]);
+        assert_eq!(removed, 2);
+
+        let (kept, removed) = Dedup::Name.apply(attendees.clone());
+        assert_eq!(names(&kept), vec!["Francois Teychene", "Fabien Bernard", "Jean-Luc Racine", "Renard Chenapan"]);
+        assert_eq!(removed, 3);
+    }
+
+    #[test]
+    fn test_dedup_keeps_present_record() {
+        let registered = attendee(Some("1"), "Francois", "Teychene", Some("francois@example.com"));
+        let present = Attende { checked_in: true, ..attendee(Some("2"), "Francois", "Teychene", Some("francois@example.com")) };
+        let (kept, removed) = Dedup::Email.apply(vec![registered.clone(), attendee(Some("3"), "Fabien", "Bernard", None), present.clone()]);
+        assert_eq!(kept[0], present);
+        assert_eq!(names(&kept), vec!["Francois Teychene", "Fabien Bernard"]);
+        assert_eq!(removed, 1);
+    }
+}
@@ -1,92 +1,93 @@
-use reqwest;
 use failure::Error;
-use std::ops::Range;
+use futures::Future;
+use futures::future::{join_all, loop_fn, Either, Loop};
 use frunk::monoid::combine_all;
-use super::model::{Profile, AttendeesResponse};
+use super::model::{Attende, AttendeesResponse};
 use super::errors::EventbriteError;
-use super::EVENTBRITE_BASE_URL;
+use super::EventbriteClient;
 
-/// Traverse a Vec<Result<T, Error>> and combine the values to return a Result<Vec<T>, Error>
-///
-/// If all values of the vector are Ok then return a Ok containing all the values cloned
-/// On the first Err it stop accumulating values and return the matched error
-///
-/// T must be a Clone type
-fn sequence<T>(seq: Vec<Result<T, Error>>) -> Result<Vec<T>, Error>
-    where T: Clone {
-    let result = seq.into_iter().fold(Ok(vec![]), |result, current|
-        result.and_then(|mut vec|
-            match current {
-                Ok(value) => {
-                    vec.push(value.clone());
-                    Ok(vec)
-                }
-                Err(e) => Err(e)
-            }));
-    result
+/// Page of attendees to request
+#[derive(Debug, Clone, PartialEq)]
+enum Page {
+    Number(u32),
+    Continuation(String),
 }
 
-fn attendees_url(event_id: &str, token: &str, page_id: u8) -> String {
-    format!("{base_url}/v3/events/{event_id}/attendees/?token={token}&page={page}", base_url = EVENTBRITE_BASE_URL, event_id = event_id, token = token, page = page_id)
+fn attendees_path(event_id: &str, page: &Page) -> String {
+    match *page {
+        Page::Number(number) => format!("/v3/events/{event_id}/attendees/?page={page}", event_id = event_id, page = number),
+        Page::Continuation(ref token) => format!("/v3/events/{event_id}/attendees/?continuation={continuation}", event_id = event_id, continuation = token)
+    }
 }
 
-fn fetch_attendees_page(event_id: &str, token: &str, page: u8) -> Result<AttendeesResponse, Error> {
-    let attendees = reqwest::get(&attendees_url(event_id, token, page))?
-        .error_for_status()?
-        .json()?;
-    Ok(attendees)
+fn fetch_attendees_page(client: &EventbriteClient, event_id: &str, page: &Page) -> impl Future<Item=AttendeesResponse, Error=Error> {
+    client.get_json(&attendees_path(event_id, page))
 }
 
-fn fetch_all_attendees<F: Fn(&str, &str, u8) -> Result<AttendeesResponse, Error>>(fetch: F, event_id: &str, token: &str) -> Result<Vec<Profile>, Error> {
-    fetch(event_id, token, 0)
-        .and_then(|result: AttendeesResponse| {
-            let range = Range { start: result.pagination.page_number, end: result.pagination.page_count };
-            sequence(range.fold(vec![Ok(result)], |mut result, page| {
-                result.push(fetch(event_id, token, page + 1));
-                result
-            }))
+/// Token of the page following the response, if eventbrite paginates with continuation tokens
+fn next_continuation(response: &AttendeesResponse) -> Option<String> {
+    if response.pagination.has_more_items {
+        response.pagination.continuation.clone()
+    } else {
+        None
+    }
+}
+
+/// Fetch the first page of attendees, then the remaining pages
+///
+/// When eventbrite provides a continuation token the pages are fetched one after the other by following the tokens,
+/// otherwise all the remaining pages are fetched concurrently by page number.
+/// The attendees are returned in the order of the pages, the first error stops the loading
+fn fetch_all_attendees<F, R>(fetch: F, event_id: &str) -> impl Future<Item=Vec<Attende>, Error=Error>
+    where F: Fn(&str, &Page) -> R,
+          R: Future<Item=AttendeesResponse, Error=Error> {
+    let event_id = event_id.to_owned();
+    let error_event_id = event_id.clone();
+    fetch(&event_id, &Page::Number(0))
+        .and_then(move |result: AttendeesResponse| match next_continuation(&result) {
+            Some(continuation) => Either::A(loop_fn((vec![result], continuation), move |(mut results, continuation)| {
+                fetch(&event_id, &Page::Continuation(continuation)).map(move |response| {
+                    let next = next_continuation(&response);
+                    results.push(response);
+                    match next {
+                        Some(continuation) => Loop::Continue((results, continuation)),
+                        None => Loop::Break(results)
+                    }
+                })
+            })),
+            None => {
+                let range = (result.pagination.page_number + 1)..=result.pagination.page_count;
+                let pages: Vec<R> = range.map(|page| fetch(&event_id, &Page::Number(page))).collect();
+                Either::B(join_all(pages).map(move |mut results| {
+                    results.insert(0, result);
+                    results
+                }))
+            }
         })
-        .map(|results: Vec<AttendeesResponse>| results.into_iter().map(|response| response.attendees.into_iter().map(|attendee| attendee.profile).collect()).collect())
-        .map(|results: Vec<Vec<Profile>>| combine_all(&results))
-        .map_err(|err| EventbriteError::AttendeesLoadError { event_id: String::from(event_id), cause: err }.into())
+        .map(|results: Vec<AttendeesResponse>| results.into_iter().map(|response| response.attendees).collect())
+        .map(|results: Vec<Vec<Attende>>| combine_all(&results))
+        .map_err(move |err| EventbriteError::AttendeesLoadError { event_id: error_event_id, cause: err }.into())
 }
 
-pub fn load_attendees(event_id: &str, token: &str) -> Result<Vec<Profile>, Error> {
-    fetch_all_attendees(fetch_attendees_page, event_id, token)
+pub fn load_attendees(client: &EventbriteClient, event_id: &str) -> impl Future<Item=Vec<Attende>, Error=Error> {
+    let client = client.clone();
+    fetch_all_attendees(move |event_id, page| fetch_attendees_page(&client, event_id, page), event_id)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use super::super::model::Pagination;
-
-    #[test]
-    fn test_attendees_url() {
-        assert_eq!(attendees_url("51124390428", "5O5ICDI5I4LUFCAZRSTX", 0), EVENTBRITE_BASE_URL.to_owned() + "/v3/events/51124390428/attendees/?token=5O5ICDI5I4LUFCAZRSTX&page=0");
-        assert_eq!(attendees_url("51124390428", "5O5ICDI5I4LUFCAZRSTX", 1), EVENTBRITE_BASE_URL.to_owned() + "/v3/events/51124390428/attendees/?token=5O5ICDI5I4LUFCAZRSTX&page=1");
-    }
+    use futures::future;
+    use super::super::model::{Pagination, Profile};
+    use failure;
+    use serde_json;
+    use std::cell::RefCell;
 
     #[test]
-    fn test_sequence() {
-        let actual = sequence(vec![Ok(0), Ok(1), Ok(2)]);
-        assert!(actual.is_ok());
-        assert_eq!(actual.unwrap(), vec![0, 1, 2]);
-
-        let actual = sequence(vec![Ok(0), Ok(1), Err(EventbriteTestError::TestError { page: 2 }.into())]);
-        assert!(actual.is_err());
-        assert_eq!(actual.unwrap_err().downcast::<EventbriteTestError>().unwrap(), EventbriteTestError::TestError { page: 2 });
-
-        let actual = sequence(vec![Ok(0), Err(EventbriteTestError::TestError { page: 1 }.into()), Ok(2)]);
-        assert!(actual.is_err());
-        assert_eq!(actual.unwrap_err().downcast::<EventbriteTestError>().unwrap(), EventbriteTestError::TestError { page: 1 });
-
-        let actual = sequence(vec![Err(EventbriteTestError::TestError { page: 0 }.into()), Ok(1), Ok(2)]);
-        assert!(actual.is_err());
-        assert_eq!(actual.unwrap_err().downcast::<EventbriteTestError>().unwrap(), EventbriteTestError::TestError { page: 0 });
-
-        let actual = sequence(vec![Err(EventbriteTestError::TestError { page: 0 }.into()), Err(EventbriteTestError::TestError { page: 1 }.into()), Ok(2)]);
-        assert!(actual.is_err());
-        assert_eq!(actual.unwrap_err().downcast::<EventbriteTestError>().unwrap(), EventbriteTestError::TestError { page: 0 });
+    fn test_attendees_path() {
+        assert_eq!(attendees_path("51124390428", &Page::Number(0)), "/v3/events/51124390428/attendees/?page=0");
+        assert_eq!(attendees_path("51124390428", &Page::Number(1)), "/v3/events/51124390428/attendees/?page=1");
+        assert_eq!(attendees_path("51124390428", &Page::Continuation("dGhpcyBpcyBwYWdlIDI".to_string())), "/v3/events/51124390428/attendees/?continuation=dGhpcyBpcyBwYWdlIDI");
     }
 
     #[test]
@@ -95,27 +96,29 @@ mod tests {
         use std::io::ErrorKind;
 
         // Right case
-        let load_function = |_event_id: &str, _token: &str, _page: u8| {
-            Ok(AttendeesResponse {
+        let load_function = |_event_id: &str, _page: &Page| {
+            future::ok(AttendeesResponse {
                 attendees: Vec::new(),
                 pagination: Pagination {
                     object_count: 0,
                     page_count: 1,
                     page_size: 0,
-                    page_number: 0,
+                    page_number: 1,
+                    has_more_items: false,
+                    continuation: None,
                 },
             })
         };
 
-        let result = fetch_all_attendees(load_function, "51124390428", "5O5ICDI5I4LUFCAZRSTX");
+        let result = fetch_all_attendees(load_function, "51124390428").wait();
         assert_eq!(result.unwrap().as_slice(), []);
 
         // Err on first call
-        let load_function = |_event_id: &str, _token: &str, _page: u8| {
-            Err(Error::new(ErrorKind::ConnectionRefused, "Fake error").into())
+        let load_function = |_event_id: &str, _page: &Page| {
+            future::err(Error::new(ErrorKind::ConnectionRefused, "Fake error").into())
         };
 
-        let result = fetch_all_attendees(load_function, "51124390428", "5O5ICDI5I4LUFCAZRSTX");
+        let result = fetch_all_attendees(load_function, "51124390428").wait();
         assert!(result.is_err());
         let typed_error = result.unwrap_err().downcast::<EventbriteError>().unwrap();
         match typed_error {
@@ -124,35 +127,167 @@ mod tests {
         }
 
         // Err on pagination loading
-        let load_function = |_event_id: &str, _token: &str, page: u8| {
-            match page {
-                0 => Ok(AttendeesResponse {
+        let load_function = |_event_id: &str, page: &Page| {
+            match *page {
+                Page::Number(0) => future::ok(AttendeesResponse {
                     attendees: Vec::new(),
                     pagination: Pagination {
                         object_count: 0,
                         page_count: 2,
                         page_size: 0,
-                        page_number: 0,
+                        page_number: 1,
+                        has_more_items: false,
+                        continuation: None,
                     },
                 }),
-                _ => Err(Error::new(ErrorKind::ConnectionRefused, "Fake error").into())
+                _ => future::err(Error::new(ErrorKind::ConnectionRefused, "Fake error").into())
             }
         };
 
-        let result = fetch_all_attendees(load_function, "51124390428", "5O5ICDI5I4LUFCAZRSTX");
+        let result = fetch_all_attendees(load_function, "51124390428").wait();
         assert!(result.is_err());
         let typed_error = result.unwrap_err().downcast::<EventbriteError>().unwrap();
         match typed_error {
             EventbriteError::AttendeesLoadError{event_id: _, cause} => assert_eq!(cause.downcast::<Error>().unwrap().kind(), ErrorKind::ConnectionRefused),
             _ => assert!(false)
         }
+
+        // Several pages loaded in order, the page 0 is served as the first page
+        let requested = RefCell::new(vec![]);
+        let load_function = |_event_id: &str, page: &Page| {
+            requested.borrow_mut().push(page.clone());
+            let page_number = match *page {
+                Page::Number(number) => number.max(1),
+                Page::Continuation(_) => unreachable!()
+            };
+            future::ok(AttendeesResponse {
+                attendees: vec![Attende::registered(Profile::new(format!("{:?}", page), "Attendee".to_string()))],
+                pagination: Pagination {
+                    object_count: 2,
+                    page_count: 2,
+                    page_size: 1,
+                    page_number: page_number,
+                    has_more_items: false,
+                    continuation: None,
+                },
+            })
+        };
+
+        let result = fetch_all_attendees(&load_function, "51124390428").wait();
+        let names: Vec<String> = result.unwrap().into_iter().map(|attendee| attendee.profile.first_name).collect();
+        assert_eq!(names, vec!["Number(0)", "Number(2)"]);
+        assert_eq!(*requested.borrow(), vec![Page::Number(0), Page::Number(2)]);
+
+        // First failing page is reported
+        let load_function = |_event_id: &str, page: &Page| {
+            match *page {
+                Page::Number(0) => future::ok(AttendeesResponse {
+                    attendees: Vec::new(),
+                    pagination: Pagination {
+                        object_count: 0,
+                        page_count: 3,
+                        page_size: 0,
+                        page_number: 1,
+                        has_more_items: false,
+                        continuation: None,
+                    },
+                }),
+                Page::Number(page) => future::err(EventbriteTestError::TestError { page: page }.into()),
+                Page::Continuation(_) => unreachable!()
+            }
+        };
+
+        let result = fetch_all_attendees(load_function, "51124390428").wait();
+        match result.unwrap_err().downcast::<EventbriteError>().unwrap() {
+            EventbriteError::AttendeesLoadError{event_id, cause} => {
+                assert_eq!(event_id, "51124390428");
+                assert_eq!(cause.downcast::<EventbriteTestError>().unwrap(), EventbriteTestError::TestError { page: 2 });
+            }
+            _ => assert!(false)
+        }
+    }
+
+    fn fixture(content: &str) -> future::FutureResult<AttendeesResponse, failure::Error> {
+        future::result(serde_json::from_str(content).map_err(|error| error.into()))
+    }
+
+    #[test]
+    fn test_fetch_all_attendees_continuation() {
+        let requested = RefCell::new(vec![]);
+        let load_function = |_event_id: &str, page: &Page| {
+            requested.borrow_mut().push(page.clone());
+            match *page {
+                Page::Number(_) => fixture(include_str!("../../fixtures/attendees_continuation_1.json")),
+                Page::Continuation(ref token) if token == "dGhpcyBpcyBwYWdlIDI" => fixture(include_str!("../../fixtures/attendees_continuation_2.json")),
+                Page::Continuation(_) => fixture(include_str!("../../fixtures/attendees_continuation_3.json"))
+            }
+        };
+
+        let result = fetch_all_attendees(&load_function, "51124390428").wait();
+        let names: Vec<String> = result.unwrap().into_iter().map(|attendee| attendee.profile.first_name).collect();
+        assert_eq!(names, vec!["Francois", "Fabien", "Jean-Luc", "Renard", "Hugo"]);
+        assert_eq!(*requested.borrow(), vec![Page::Number(0), Page::Continuation("dGhpcyBpcyBwYWdlIDI".to_string()), Page::Continuation("dGhpcyBpcyBwYWdlIDM".to_string())]);
+
+        // Error on a continuation page
+        let load_function = |_event_id: &str, page: &Page| {
+            match *page {
+                Page::Number(_) => fixture(include_str!("../../fixtures/attendees_continuation_1.json")),
+                Page::Continuation(_) => future::err(EventbriteTestError::TestError { page: 2 }.into())
+            }
+        };
+
+        let result = fetch_all_attendees(load_function, "51124390428").wait();
+        match result.unwrap_err().downcast::<EventbriteError>().unwrap() {
+            EventbriteError::AttendeesLoadError{event_id: _, cause} => assert_eq!(cause.downcast::<EventbriteTestError>().unwrap(), EventbriteTestError::TestError { page: 2 }),
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn test_fetch_all_attendees_large_event() {
+        let load_function = |_event_id: &str, page: &Page| {
+            let page_number = match *page {
+                Page::Number(number) => number.max(1),
+                Page::Continuation(_) => unreachable!()
+            };
+            future::ok(AttendeesResponse {
+                attendees: vec![Attende::registered(Profile::new(format!("{}", page_number), "Attendee".to_string()))],
+                pagination: Pagination {
+                    object_count: 300,
+                    page_count: 300,
+                    page_size: 1,
+                    page_number: page_number,
+                    has_more_items: page_number < 300,
+                    continuation: None,
+                },
+            })
+        };
+
+        let result = fetch_all_attendees(load_function, "51124390428").wait().unwrap();
+        assert_eq!(result.len(), 300);
+        assert_eq!(result.first().unwrap().profile.first_name, "1");
+        assert_eq!(result.last().unwrap().profile.first_name, "300");
+    }
+
+    #[test]
+    fn test_pagination_deserialization() {
+        let response: AttendeesResponse = serde_json::from_str(r#"{"pagination": {"object_count": 1234, "page_number": 1, "page_size": 50, "page_count": 25}, "attendees": []}"#).unwrap();
+        assert_eq!(response.pagination.object_count, 1234);
+        assert!(!response.pagination.has_more_items);
+        assert_eq!(response.pagination.continuation, None);
+
+        let response: AttendeesResponse = serde_json::from_str(include_str!("../../fixtures/attendees_continuation_2.json")).unwrap();
+        assert_eq!(response.pagination.page_number, 2);
+        assert!(response.pagination.has_more_items);
+        assert_eq!(response.pagination.continuation, Some("dGhpcyBpcyBwYWdlIDM".to_string()));
+        assert_eq!(response.attendees.len(), 2);
     }
 
     #[derive(Debug, Fail, PartialEq)]
     enum EventbriteTestError {
         #[fail(display = "Unexpected Error for tests")]
         TestError {
-            page: u8
+            page: u32
         }
     }
 }
\ No newline at end of file
@@ -1,4 +1,5 @@
 use failure::Error;
+use std::time::Duration;
 
 #[derive(Debug, Fail)]
 pub enum EventbriteError {
@@ -8,5 +9,14 @@ pub enum EventbriteError {
         #[cause] cause: Error
     },
     #[fail(display = "No event available on eventbrite")]
-    NoEventAvailable
+    NoEventAvailable,
+    #[fail(display = "Too many requests sent to eventbrite")]
+    RateLimited {
+        /// Delay asked by eventbrite in the `Retry-After` header
+        retry_after: Option<Duration>
+    },
+    #[fail(display = "Invalid eventbrite webhook for {}", api_url)]
+    InvalidWebhook {
+        api_url: String
+    }
 }
@@ -1,33 +1,20 @@
-use reqwest;
 use failure::Error;
-use super::errors::EventbriteError;
+use futures::Future;
 use super::model::{Event, EventsResponse};
-use super::EVENTBRITE_BASE_URL;
+use super::EventbriteClient;
 
 
-fn events_url(organizer: &str, token: &str) -> String {
-    format!("{base_url}/v3/organizations/{organizer}/events/?status=live&order_by=start_desc&token={token}", base_url = EVENTBRITE_BASE_URL, organizer = organizer, token = token)
+fn events_path(organizer: &str) -> String {
+    format!("/v3/organizations/{organizer}/events/?status=live&order_by=start_desc&expand=venue", organizer = organizer)
 }
 
-fn load_events(organizer: &str, token: &str) -> Result<EventsResponse, Error> {
-    let events = reqwest::get(&events_url(organizer, token))?
-        .error_for_status()?
-        .json()?;
-    Ok(events)
+fn load_events(client: &EventbriteClient) -> impl Future<Item=EventsResponse, Error=Error> {
+    client.get_json(&events_path(&client.organizer))
 }
 
-fn first_event(events: EventsResponse) -> Result<Event, Error> {
-    events.events.first()
-        .map(|reference| reference.clone())
-        .ok_or(EventbriteError::NoEventAvailable.into())
-}
-
-fn fetch_first_event<F: Fn(&str, &str) -> Result<EventsResponse, Error>>(fetch: F, organizer: &str, token: &str) -> Result<Event, Error> {
-    fetch(organizer, token).and_then(first_event)
-}
-
-pub fn get_current_event(organizer: &str, token: &str) -> Result<Event, Error> {
-    fetch_first_event(load_events, organizer, token)
+/// Live events of the organizer, the most recent first
+pub fn get_live_events(client: &EventbriteClient) -> impl Future<Item=Vec<Event>, Error=Error> {
+    load_events(client).map(|response| response.events)
 }
 
 
@@ -37,60 +24,8 @@ mod tests {
 
 
     #[test]
-    fn test_events_url() {
-        assert_eq!(events_url("412451CDS", "5O5ICDI5I4LUFCAZRSTX"), EVENTBRITE_BASE_URL.to_owned() + "/v3/organizations/412451CDS/events/?status=live&order_by=start_desc&token=5O5ICDI5I4LUFCAZRSTX");
-    }
-
-    #[test]
-    fn test_first_event() {
-        let response = EventsResponse{ events: vec![Event{id: "51124390428".to_string()}]};
-        let actual = first_event(response);
-        assert!(actual.is_ok());
-        assert_eq!(actual.unwrap(), Event{id: "51124390428".to_string()});
-
-        let response = EventsResponse{ events: vec![]};
-        let actual = first_event(response);
-        assert!(actual.is_err());
-        matches!(actual.unwrap_err().downcast::<EventbriteError>(), Ok(EventbriteError::NoEventAvailable));
-
-        let response = EventsResponse{ events: vec![Event{id: "51124390432".to_string()}, Event{id: "51124390428".to_string()}]};
-        let actual = first_event(response);
-        assert!(actual.is_ok());
-        assert_eq!(actual.unwrap(), Event{id: "51124390432".to_string()});
-    }
-
-    #[test]
-    fn test_fetch_first_event() {
-        use std::io::Error;
-        use std::io::ErrorKind;
-
-        let fetch = |_organizer: &str, _token: &str| {
-            Ok(EventsResponse{events: vec![Event{id: "51124390428".to_string()}]})
-        };
-        let actual = fetch_first_event(fetch, "412451CDS", "5O5ICDI5I4LUFCAZRSTX");
-        assert!(actual.is_ok());
-        assert_eq!(actual.unwrap(), Event{id: "51124390428".to_string()});
-
-        let fetch = |_organizer: &str, _token: &str| {
-            Ok(EventsResponse{events: vec![]})
-        };
-        let actual = fetch_first_event(fetch, "412451CDS", "5O5ICDI5I4LUFCAZRSTX");
-        assert!(actual.is_err());
-        matches!(actual.unwrap_err().downcast::<EventbriteError>(), Ok(EventbriteError::NoEventAvailable));
-
-        let fetch = |_organizer: &str, _token: &str| {
-            Err(Error::new(ErrorKind::ConnectionRefused, "Fake error").into())
-        };
-        let actual = fetch_first_event(fetch, "412451CDS", "5O5ICDI5I4LUFCAZRSTX");
-        assert!(actual.is_err());
-        matches!(actual.unwrap_err().downcast::<Error>(), Ok(ref e) if e.kind() == ErrorKind::ConnectionRefused);
-
-        let fetch = |_organizer: &str, _token: &str| {
-            Ok(EventsResponse{events: vec![Event{id: "51124390432".to_string()}, Event{id: "51124390428".to_string()}]})
-        };
-        let actual = fetch_first_event(fetch, "412451CDS", "5O5ICDI5I4LUFCAZRSTX");
-        assert!(actual.is_ok());
-        assert_eq!(actual.unwrap(), Event{id: "51124390432".to_string()});
+    fn test_events_path() {
+        assert_eq!(events_path("412451CDS"), "/v3/organizations/412451CDS/events/?status=live&order_by=start_desc&expand=venue");
     }
 
 }
\ No newline at end of file
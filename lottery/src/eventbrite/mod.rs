@@ -1,7 +1,105 @@
+use failure::Error;
+use futures::Future;
+use reqwest::StatusCode;
+use reqwest::async::{Client, RequestBuilder, Response};
+use reqwest::header::RETRY_AFTER;
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::time::Duration;
+use self::errors::EventbriteError;
+use self::retry::RetryPolicy;
+use source::{AttendeeSource, SourceFuture};
+use self::model::{Event, Attende};
+
 pub mod errors;
 
 pub mod model;
 pub mod attendees;
 pub mod events;
+pub mod retry;
+pub mod webhooks;
+
+/// Default url of the eventbrite API
+pub const EVENTBRITE_BASE_URL: &'static str = "https://www.eventbriteapi.com";
+
+/// Client of the eventbrite API for the live events of an organization and their attendees
+///
+/// The token is never part of the urls and is redacted from the debug output, so it does not end up in logs
+#[derive(Clone)]
+pub struct EventbriteClient {
+    /// Url of the API without trailing slash, can target a proxy or a local stand-in server
+    pub base_url: String,
+    pub organizer: String,
+    pub token: String,
+    pub retry: RetryPolicy,
+    http: Client,
+}
+
+impl EventbriteClient {
+    pub fn new(base_url: String, organizer: String, token: String) -> Self {
+        EventbriteClient { base_url: base_url.trim_end_matches('/').to_owned(), organizer: organizer, token: token, retry: RetryPolicy::default(), http: Client::new() }
+    }
+
+    /// GET request on a path of the API, authenticated with the token
+    fn get(&self, path: &str) -> RequestBuilder {
+        self.http.get(&format!("{}{}", self.base_url, path))
+            .bearer_auth(&self.token)
+    }
+
+    /// GET a path of the API and deserialize the JSON response, the failed requests are retried according to the retry policy
+    fn get_json<T: DeserializeOwned + 'static>(&self, path: &str) -> impl Future<Item=T, Error=Error> {
+        let client = self.clone();
+        let path = path.to_owned();
+        retry::retry(&self.retry, move || client.get(&path)
+            .send()
+            .from_err()
+            .and_then(check_status)
+            .and_then(|mut response| response.json().from_err()), retry::sleep)
+    }
+}
+
+/// Turn the error statuses into errors, a rate limited request carries the delay asked by eventbrite
+fn check_status(response: Response) -> Result<Response, Error> {
+    if response.status() == StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = response.headers().get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+            .map(Duration::from_secs);
+        return Err(EventbriteError::RateLimited { retry_after: retry_after }.into());
+    }
+    response.error_for_status().map_err(|error| error.into())
+}
+
+impl fmt::Debug for EventbriteClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EventbriteClient")
+            .field("base_url", &self.base_url)
+            .field("organizer", &self.organizer)
+            .field("token", &"<redacted>")
+            .field("retry", &self.retry)
+            .finish()
+    }
+}
+
+impl AttendeeSource for EventbriteClient {
+    fn events(&self) -> SourceFuture<Vec<Event>> {
+        Box::new(events::get_live_events(self))
+    }
+
+    fn attendees(&self, event: &Event) -> SourceFuture<Vec<Attende>> {
+        Box::new(attendees::load_attendees(self, &event.id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-const EVENTBRITE_BASE_URL: &'static str = "https://www.eventbriteapi.com";
\ No newline at end of file
+    #[test]
+    fn test_debug_redacts_token() {
+        let client = EventbriteClient::new(EVENTBRITE_BASE_URL.to_owned(), "412451CDS".to_owned(), "5O5ICDI5I4LUFCAZRSTX".to_owned());
+        let debug = format!("{:?}", client);
+        assert!(debug.contains("412451CDS"));
+        assert!(!debug.contains("5O5ICDI5I4LUFCAZRSTX"));
+    }
+}
\ No newline at end of file
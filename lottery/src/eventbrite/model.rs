@@ -1,20 +1,120 @@
 #[derive(Deserialize, Debug, Clone)]
 pub struct Pagination {
-    pub object_count: u8,
-    pub page_count: u8,
-    pub page_size: u8,
-    pub page_number: u8
+    pub object_count: u32,
+    pub page_count: u32,
+    pub page_size: u32,
+    pub page_number: u32,
+    #[serde(default)]
+    pub has_more_items: bool,
+    /// Token of the next page, provided by eventbrite instead of page numbers on large lists
+    #[serde(default)]
+    pub continuation: Option<String>
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+/// Person taking part in a draw, the email is not part of it since the draws are public
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Profile {
     pub first_name: String,
-    pub last_name: String
+    pub last_name: String,
+    /// Id of the attendee on eventbrite, None for the attendees loaded from a file or added manually
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>
 }
 
-#[derive(Deserialize, Debug, Clone)]
+impl Profile {
+    /// Profile known only by its names
+    pub fn new<F: Into<String>, L: Into<String>>(first_name: F, last_name: L) -> Self {
+        Profile { first_name: first_name.into(), last_name: last_name.into(), id: None }
+    }
+
+    /// Same attendee id when both profiles have one, same names otherwise
+    pub fn same_person(&self, other: &Profile) -> bool {
+        match (&self.id, &other.id) {
+            (&Some(ref id), &Some(ref other_id)) => id == other_id,
+            _ => self.first_name == other.first_name && self.last_name == other.last_name
+        }
+    }
+
+    /// Profile without the attendee id, for the data recorded by names
+    pub fn without_id(&self) -> Profile {
+        Profile::new(self.first_name.clone(), self.last_name.clone())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(from = "EventbriteAttende")]
 pub struct Attende {
-    pub profile: Profile
+    pub profile: Profile,
+    pub email: Option<String>,
+    pub checked_in: bool,
+    pub cancelled: bool,
+    pub refunded: bool,
+    pub status: Option<String>
+}
+
+/// Profile as provided by eventbrite, with the email of the attendee
+#[derive(Deserialize)]
+struct EventbriteProfile {
+    first_name: String,
+    last_name: String,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default)]
+    id: Option<String>
+}
+
+/// Attendee as provided by eventbrite, the id is read next to the profile and the email from the profile.
+/// Once serialized the id is part of the profile and the email is next to it
+#[derive(Deserialize)]
+struct EventbriteAttende {
+    #[serde(default)]
+    id: Option<String>,
+    profile: EventbriteProfile,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default)]
+    checked_in: bool,
+    #[serde(default)]
+    cancelled: bool,
+    #[serde(default)]
+    refunded: bool,
+    #[serde(default)]
+    status: Option<String>
+}
+
+impl From<EventbriteAttende> for Attende {
+    fn from(attendee: EventbriteAttende) -> Self {
+        Attende {
+            email: attendee.email.or(attendee.profile.email),
+            profile: Profile { first_name: attendee.profile.first_name, last_name: attendee.profile.last_name, id: attendee.id.or(attendee.profile.id) },
+            checked_in: attendee.checked_in,
+            cancelled: attendee.cancelled,
+            refunded: attendee.refunded,
+            status: attendee.status
+        }
+    }
+}
+
+impl Attende {
+    /// Attendee registered for the event with no information about its presence
+    pub fn registered(profile: Profile) -> Self {
+        Attende { profile: profile, email: None, checked_in: false, cancelled: false, refunded: false, status: None }
+    }
+
+    /// Attendee physically present at the event
+    pub fn present(profile: Profile) -> Self {
+        Attende { profile: profile, email: None, checked_in: true, cancelled: false, refunded: false, status: Some("Checked In".to_owned()) }
+    }
+
+    /// Neither cancelled nor refunded, the attendee can win
+    pub fn is_attending(&self) -> bool {
+        !self.cancelled && !self.refunded
+    }
+
+    /// Checked in and neither cancelled nor refunded
+    pub fn is_present(&self) -> bool {
+        self.checked_in && self.is_attending()
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -23,12 +123,146 @@ pub struct AttendeesResponse {
     pub pagination: Pagination
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+/// Text provided by Eventbrite both as plain text and html
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct MultipartText {
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub html: Option<String>
+}
+
+/// Date of an event, `local` and `utc` are ISO 8601 date times
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct EventDate {
+    pub timezone: String,
+    pub local: String,
+    pub utc: String
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Address {
+    #[serde(default)]
+    pub address_1: Option<String>,
+    #[serde(default)]
+    pub address_2: Option<String>,
+    #[serde(default)]
+    pub city: Option<String>,
+    #[serde(default)]
+    pub postal_code: Option<String>,
+    #[serde(default)]
+    pub country: Option<String>,
+    #[serde(default)]
+    pub localized_address_display: Option<String>
+}
+
+/// Venue of an event, only provided by Eventbrite when the venue is expanded
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Venue {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub address: Option<Address>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
 pub struct Event {
-    pub id: String
+    pub id: String,
+    #[serde(default)]
+    pub name: Option<MultipartText>,
+    #[serde(default)]
+    pub start: Option<EventDate>,
+    #[serde(default)]
+    pub end: Option<EventDate>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub capacity: Option<u32>,
+    #[serde(default)]
+    pub venue: Option<Venue>
+}
+
+impl Event {
+    /// Event known only by its id
+    pub fn new(id: String) -> Self {
+        Event { id: id, ..Default::default() }
+    }
+}
+
+/// Order of tickets for an event, the attendees are only provided when they are expanded
+#[derive(Deserialize, Debug, Clone)]
+pub struct Order {
+    pub id: String,
+    pub event_id: String,
+    #[serde(default)]
+    pub attendees: Vec<Attende>
 }
 
 #[derive(Deserialize, Debug)]
 pub struct EventsResponse {
     pub events: Vec<Event>
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn test_attende_deserialization() {
+        let attende: Attende = serde_json::from_str(r#"{"id": "1060946531", "profile": {"first_name": "Francois", "last_name": "Teychene", "email": "francois@example.com"}, "checked_in": true, "cancelled": false, "refunded": false, "status": "Checked In"}"#).unwrap();
+        let profile = Profile { first_name: "Francois".to_string(), last_name: "Teychene".to_string(), id: Some("1060946531".to_string()) };
+        let expected = Attende { email: Some("francois@example.com".to_string()), ..Attende::present(profile) };
+        assert_eq!(attende, expected);
+        assert!(attende.is_present());
+        assert!(attende.is_attending());
+
+        // Serialized in the snapshots
+        let serialized = serde_json::to_string(&attende).unwrap();
+        assert_eq!(serde_json::from_str::<Attende>(&serialized).unwrap(), expected);
+
+        let attende: Attende = serde_json::from_str(r#"{"profile": {"first_name": "Francois", "last_name": "Teychene"}}"#).unwrap();
+        assert_eq!(attende, Attende::registered(Profile::new("Francois".to_string(), "Teychene".to_string())));
+        assert!(!attende.is_present());
+        assert!(attende.is_attending());
+
+        let attende: Attende = serde_json::from_str(r#"{"profile": {"first_name": "Francois", "last_name": "Teychene"}, "checked_in": true, "cancelled": false, "refunded": true, "status": "Not Attending"}"#).unwrap();
+        assert!(!attende.is_present());
+        assert!(!attende.is_attending());
+    }
+
+    #[test]
+    fn test_same_person() {
+        let francois = Profile::new("Francois".to_string(), "Teychene".to_string());
+        let with_id = |id: &str| Profile { id: Some(id.to_string()), ..francois.clone() };
+        assert!(francois.same_person(&francois));
+        assert!(francois.same_person(&with_id("1060946531")));
+        assert!(with_id("1060946531").same_person(&with_id("1060946531")));
+        assert!(!with_id("1060946531").same_person(&with_id("1060946536")));
+        assert!(!francois.same_person(&Profile::new("Fabien".to_string(), "Teychene".to_string())));
+        assert_eq!(with_id("1060946531").without_id(), francois);
+    }
+
+    #[test]
+    fn test_event_deserialization() {
+        let event: Event = serde_json::from_str(r#"{
+            "id": "51124390428",
+            "name": {"text": "Soirée Rust", "html": "Soirée Rust"},
+            "url": "https://www.eventbrite.fr/e/soiree-rust-51124390428",
+            "start": {"timezone": "Europe/Paris", "local": "2018-11-21T19:00:00", "utc": "2018-11-21T18:00:00Z"},
+            "end": {"timezone": "Europe/Paris", "local": "2018-11-21T22:00:00", "utc": "2018-11-21T21:00:00Z"},
+            "capacity": 120,
+            "venue_id": "27437428",
+            "venue": {"name": "Epitech", "address": {"address_1": "40 Boulevard de la Marquette", "city": "Toulouse", "postal_code": "31000", "country": "FR", "localized_address_display": "40 Boulevard de la Marquette, 31000 Toulouse"}}
+        }"#).unwrap();
+        assert_eq!(event.id, "51124390428");
+        assert_eq!(event.name.and_then(|name| name.text), Some("Soirée Rust".to_string()));
+        assert_eq!(event.start.map(|start| start.utc), Some("2018-11-21T18:00:00Z".to_string()));
+        assert_eq!(event.end.map(|end| end.local), Some("2018-11-21T22:00:00".to_string()));
+        assert_eq!(event.capacity, Some(120));
+        assert_eq!(event.venue.and_then(|venue| venue.address).and_then(|address| address.city), Some("Toulouse".to_string()));
+
+        let event: Event = serde_json::from_str(r#"{"id": "51124390428"}"#).unwrap();
+        assert_eq!(event, Event::new("51124390428".to_string()));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,173 @@
+use failure::Error;
+use futures::{Future, IntoFuture};
+use futures::future::{loop_fn, Either, Loop};
+use reqwest;
+use std::cmp::min;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use tokio_timer::Delay;
+use super::errors::EventbriteError;
+
+/// Retries of the failed eventbrite requests, with an exponential backoff between the attempts
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt, 0 to never retry
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled on every retry
+    pub initial_delay: Duration,
+    /// Upper bound of the delay between two attempts, a longer `Retry-After` of eventbrite is not waited for
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy { max_retries: 3, initial_delay: Duration::from_millis(500), max_delay: Duration::from_secs(30) }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the retry following the failed attempt (starting at 0), None if the error should not be retried
+    ///
+    /// Rate limited requests wait for the delay asked by eventbrite, server errors and network errors use the backoff.
+    /// A rate limited request is not retried when eventbrite asks to wait longer than `max_delay`, retrying earlier would
+    /// be rejected again. Client errors and invalid responses are never retried
+    pub fn delay(&self, attempt: u32, error: &Error) -> Option<Duration> {
+        if attempt >= self.max_retries {
+            return None;
+        }
+        let backoff = min(self.initial_delay.checked_mul(1 << min(attempt, 16)).unwrap_or(self.max_delay), self.max_delay);
+        if let Some(&EventbriteError::RateLimited { retry_after }) = error.downcast_ref::<EventbriteError>() {
+            return match retry_after {
+                Some(retry_after) if retry_after > self.max_delay => None,
+                Some(retry_after) => Some(retry_after),
+                None => Some(backoff)
+            };
+        }
+        if let Some(error) = error.downcast_ref::<reqwest::Error>() {
+            match error.status() {
+                Some(status) if status.is_server_error() => Some(backoff),
+                Some(_) => None,
+                None if error.is_serialization() => None,
+                None => Some(backoff)
+            }
+        } else {
+            None
+        }
+    }
+}
+
+/// Timer used between the attempts, needs to run in a tokio runtime
+pub fn sleep(duration: Duration) -> impl Future<Item=(), Error=Error> {
+    Delay::new(Instant::now() + duration).from_err()
+}
+
+/// Run the operation until it succeeds or the policy stops retrying, the last error is returned
+pub fn retry<F, R, S, D>(policy: &RetryPolicy, operation: F, sleep: S) -> impl Future<Item=R::Item, Error=Error>
+    where F: Fn() -> R,
+          R: IntoFuture<Error=Error>,
+          S: Fn(Duration) -> D,
+          D: IntoFuture<Item=(), Error=Error> {
+    let policy = policy.clone();
+    let sleep = Rc::new(sleep);
+    loop_fn(0, move |attempt| {
+        let policy = policy.clone();
+        let sleep = sleep.clone();
+        operation().into_future()
+            .map(Loop::Break)
+            .or_else(move |error| match policy.delay(attempt, &error) {
+                Some(duration) => {
+                    warn!("Eventbrite request failed ({}), retry in {:?}", error, duration);
+                    Either::A(sleep(duration).into_future().map(move |_| Loop::Continue(attempt + 1)))
+                }
+                None => Either::B(Err(error).into_future())
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use std::cell::RefCell;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy { max_retries: 3, initial_delay: Duration::from_millis(100), max_delay: Duration::from_secs(1) }
+    }
+
+    #[test]
+    fn test_delay() {
+        let policy = policy();
+        let rate_limited: Error = EventbriteError::RateLimited { retry_after: Some(Duration::from_millis(700)) }.into();
+        assert_eq!(policy.delay(0, &rate_limited), Some(Duration::from_millis(700)));
+        assert_eq!(policy.delay(3, &rate_limited), None);
+
+        let rate_limited: Error = EventbriteError::RateLimited { retry_after: None }.into();
+        assert_eq!(policy.delay(0, &rate_limited), Some(Duration::from_millis(100)));
+        assert_eq!(policy.delay(1, &rate_limited), Some(Duration::from_millis(200)));
+        assert_eq!(policy.delay(2, &rate_limited), Some(Duration::from_millis(400)));
+
+        let rate_limited: Error = EventbriteError::RateLimited { retry_after: Some(Duration::from_secs(1)) }.into();
+        assert_eq!(policy.delay(0, &rate_limited), Some(Duration::from_secs(1)));
+
+        // Eventbrite asks to wait longer than the policy accepts, the request fails
+        let rate_limited: Error = EventbriteError::RateLimited { retry_after: Some(Duration::from_secs(3600)) }.into();
+        assert_eq!(policy.delay(0, &rate_limited), None);
+
+        // The backoff is capped
+        let rate_limited: Error = EventbriteError::RateLimited { retry_after: None }.into();
+        let more_retries = RetryPolicy { max_retries: 10, ..policy.clone() };
+        assert_eq!(more_retries.delay(5, &rate_limited), Some(Duration::from_secs(1)));
+
+        let other: Error = EventbriteError::NoEventAvailable.into();
+        assert_eq!(policy.delay(0, &other), None);
+    }
+
+    #[test]
+    fn test_retry() {
+        let attempts = RefCell::new(0);
+        let delays = RefCell::new(vec![]);
+        let operation = || {
+            *attempts.borrow_mut() += 1;
+            match *attempts.borrow() {
+                1 => Err(EventbriteError::RateLimited { retry_after: Some(Duration::from_millis(300)) }.into()),
+                2 => Err(EventbriteError::RateLimited { retry_after: None }.into()),
+                attempt => Ok(attempt)
+            }
+        };
+        let sleep = |duration| {
+            delays.borrow_mut().push(duration);
+            future::ok(())
+        };
+        assert_eq!(retry(&policy(), operation, sleep).wait().unwrap(), 3);
+        assert_eq!(*delays.borrow(), vec![Duration::from_millis(300), Duration::from_millis(200)]);
+
+        // Retries exhausted
+        let attempts = RefCell::new(0);
+        let operation = || -> Result<u32, Error> {
+            *attempts.borrow_mut() += 1;
+            Err(EventbriteError::RateLimited { retry_after: None }.into())
+        };
+        let actual = retry(&policy(), operation, |_| future::ok(())).wait();
+        assert!(matches!(actual.unwrap_err().downcast::<EventbriteError>(), Ok(EventbriteError::RateLimited { .. })));
+        assert_eq!(*attempts.borrow(), 4);
+
+        // Not retried
+        let attempts = RefCell::new(0);
+        let operation = || -> Result<u32, Error> {
+            *attempts.borrow_mut() += 1;
+            Err(EventbriteError::NoEventAvailable.into())
+        };
+        assert!(retry(&policy(), operation, |_| future::ok(())).wait().is_err());
+        assert_eq!(*attempts.borrow(), 1);
+
+        // Waiting longer than the policy accepts, the rate limit is returned without retry
+        let attempts = RefCell::new(0);
+        let operation = || -> Result<u32, Error> {
+            *attempts.borrow_mut() += 1;
+            Err(EventbriteError::RateLimited { retry_after: Some(Duration::from_secs(3600)) }.into())
+        };
+        let actual = retry(&policy(), operation, |_| future::ok(())).wait();
+        assert!(matches!(actual.unwrap_err().downcast::<EventbriteError>(), Ok(EventbriteError::RateLimited { retry_after: Some(retry_after) }) if retry_after == Duration::from_secs(3600)));
+        assert_eq!(*attempts.borrow(), 1);
+    }
+}
@@ -0,0 +1,163 @@
+use failure::Error;
+use futures::Future;
+use super::errors::EventbriteError;
+use super::model::{Attende, Order};
+use super::EventbriteClient;
+
+/// Configuration of the eventbrite webhook which sent a payload
+#[derive(Deserialize, Debug, Clone)]
+pub struct WebhookConfig {
+    /// Action which triggered the webhook, like `order.placed`
+    pub action: String,
+    #[serde(default)]
+    pub webhook_id: Option<String>,
+    #[serde(default)]
+    pub user_id: Option<String>,
+    #[serde(default)]
+    pub endpoint_url: Option<String>
+}
+
+/// Payload posted by eventbrite, only the url of the changed object is provided
+#[derive(Deserialize, Debug, Clone)]
+pub struct WebhookPayload {
+    pub config: WebhookConfig,
+    pub api_url: String
+}
+
+/// Object of the eventbrite API changed by a webhook action handled by the lottery
+#[derive(Debug, Clone, PartialEq)]
+pub enum WebhookResource {
+    /// A new order, sent on `order.placed`
+    Order { order_id: String },
+    /// An attendee, sent on `attendee.updated` and `attendee.checked_in`
+    Attendee { event_id: String, attendee_id: String },
+}
+
+/// Ids in the url of the API, eventbrite ids are alphanumeric
+fn is_id(segment: &str) -> bool {
+    !segment.is_empty() && segment.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+impl WebhookPayload {
+    /// Object to load for the action, None if the action is not handled by the lottery
+    ///
+    /// Eventbrite does not sign the payloads, so nothing but the url of the object is used and the object is loaded from
+    /// the API with the token. The url must target the API of the client and match the action, so a forged payload
+    /// cannot make the lottery send its token elsewhere
+    pub fn resource(&self, base_url: &str) -> Result<Option<WebhookResource>, EventbriteError> {
+        let invalid = || EventbriteError::InvalidWebhook { api_url: self.api_url.clone() };
+        let base_url = base_url.trim_end_matches('/');
+        let path = match self.api_url.get(base_url.len()..) {
+            Some(path) if self.api_url.starts_with(base_url) && path.starts_with("/v3/") => path,
+            _ => return Err(invalid())
+        };
+        let segments: Vec<&str> = path["/v3/".len()..].trim_end_matches('/').split('/').collect();
+        let resource = match (self.config.action.as_str(), segments.as_slice()) {
+            ("order.placed", &["orders", order_id]) if is_id(order_id) =>
+                WebhookResource::Order { order_id: order_id.to_owned() },
+            ("attendee.updated", &["events", event_id, "attendees", attendee_id]) |
+            ("attendee.checked_in", &["events", event_id, "attendees", attendee_id]) if is_id(event_id) && is_id(attendee_id) =>
+                WebhookResource::Attendee { event_id: event_id.to_owned(), attendee_id: attendee_id.to_owned() },
+            ("order.placed", _) | ("attendee.updated", _) | ("attendee.checked_in", _) => return Err(invalid()),
+            _ => return Ok(None)
+        };
+        Ok(Some(resource))
+    }
+}
+
+/// Compare the secret of a webhook with the expected one in a time not depending on the first differing byte
+pub fn valid_secret(expected: &str, provided: &str) -> bool {
+    expected.len() == provided.len() &&
+        expected.bytes().zip(provided.bytes()).fold(0, |diff, (expected, provided)| diff | (expected ^ provided)) == 0
+}
+
+fn order_path(order_id: &str) -> String {
+    format!("/v3/orders/{order_id}/?expand=attendees", order_id = order_id)
+}
+
+fn attendee_path(event_id: &str, attendee_id: &str) -> String {
+    format!("/v3/events/{event_id}/attendees/{attendee_id}/", event_id = event_id, attendee_id = attendee_id)
+}
+
+/// Order with its attendees, loaded on `order.placed`
+pub fn load_order(client: &EventbriteClient, order_id: &str) -> impl Future<Item=Order, Error=Error> {
+    client.get_json(&order_path(order_id))
+}
+
+/// Attendee of an event, loaded on `attendee.updated` and `attendee.checked_in`
+pub fn load_attendee(client: &EventbriteClient, event_id: &str, attendee_id: &str) -> impl Future<Item=Attende, Error=Error> {
+    client.get_json(&attendee_path(event_id, attendee_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+    use super::super::EVENTBRITE_BASE_URL;
+
+    fn payload(action: &str, api_url: &str) -> WebhookPayload {
+        WebhookPayload {
+            config: WebhookConfig { action: action.to_string(), webhook_id: None, user_id: None, endpoint_url: None },
+            api_url: api_url.to_string()
+        }
+    }
+
+    #[test]
+    fn test_webhook_resource() {
+        let order = payload("order.placed", "https://www.eventbriteapi.com/v3/orders/853311187/");
+        assert_eq!(order.resource(EVENTBRITE_BASE_URL).unwrap(), Some(WebhookResource::Order { order_id: "853311187".to_string() }));
+        assert_eq!(order_path("853311187"), "/v3/orders/853311187/?expand=attendees");
+
+        let attendee = payload("attendee.checked_in", "https://www.eventbriteapi.com/v3/events/51124390428/attendees/1060946539/");
+        let expected = WebhookResource::Attendee { event_id: "51124390428".to_string(), attendee_id: "1060946539".to_string() };
+        assert_eq!(attendee.resource(EVENTBRITE_BASE_URL).unwrap(), Some(expected.clone()));
+        assert_eq!(attendee_path("51124390428", "1060946539"), "/v3/events/51124390428/attendees/1060946539/");
+        let attendee = payload("attendee.updated", "http://127.0.0.1:8089/v3/events/51124390428/attendees/1060946539/");
+        assert_eq!(attendee.resource("http://127.0.0.1:8089/").unwrap(), Some(expected));
+
+        // Actions not handled are ignored
+        assert_eq!(payload("test", "https://www.eventbriteapi.com/v3/users/me/").resource(EVENTBRITE_BASE_URL).unwrap(), None);
+        assert_eq!(payload("event.published", "https://www.eventbriteapi.com/v3/events/51124390428/").resource(EVENTBRITE_BASE_URL).unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid_webhook_resource() {
+        let invalid = |action: &str, api_url: &str| matches!(payload(action, api_url).resource(EVENTBRITE_BASE_URL), Err(EventbriteError::InvalidWebhook { .. }));
+        assert!(invalid("order.placed", "https://attacker.example.com/v3/orders/853311187/"));
+        assert!(invalid("order.placed", "https://www.eventbriteapi.com.example.com/v3/orders/853311187/"));
+        assert!(invalid("order.placed", "https://www.eventbriteapi.com/v3/events/51124390428/attendees/1060946539/"));
+        assert!(invalid("order.placed", "https://www.eventbriteapi.com/v3/orders/853311187/?expand=event"));
+        assert!(invalid("order.placed", "https://www.eventbriteapi.com/v3/orders/../users/me/"));
+        assert!(invalid("attendee.updated", "https://www.eventbriteapi.com/v3/orders/853311187/"));
+        assert!(invalid("attendee.checked_in", "https://www.eventbriteapi.com/v3/events/51124390428/attendees/"));
+    }
+
+    #[test]
+    fn test_valid_secret() {
+        assert!(valid_secret("webhook-secret", "webhook-secret"));
+        assert!(!valid_secret("webhook-secret", "webhook-secreT"));
+        assert!(!valid_secret("webhook-secret", "webhook-secret "));
+        assert!(!valid_secret("webhook-secret", ""));
+    }
+
+    #[test]
+    fn test_webhook_deserialization() {
+        let payload: WebhookPayload = serde_json::from_str(r#"{
+            "config": {"action": "order.placed", "user_id": "163054428874", "endpoint_url": "https://lottery.example.com/webhooks/eventbrite", "webhook_id": "1092315"},
+            "api_url": "https://www.eventbriteapi.com/v3/orders/853311187/"
+        }"#).unwrap();
+        assert_eq!(payload.config.action, "order.placed");
+        assert_eq!(payload.config.webhook_id, Some("1092315".to_string()));
+
+        let order: Order = serde_json::from_str(r#"{
+            "id": "853311187",
+            "event_id": "51124390428",
+            "attendees": [{"profile": {"first_name": "Francois", "last_name": "Teychene"}, "checked_in": false, "cancelled": false, "refunded": false, "status": "Attending"}]
+        }"#).unwrap();
+        assert_eq!(order.event_id, "51124390428");
+        assert_eq!(order.attendees.len(), 1);
+
+        let order: Order = serde_json::from_str(r#"{"id": "853311187", "event_id": "51124390428"}"#).unwrap();
+        assert!(order.attendees.is_empty());
+    }
+}
@@ -0,0 +1,180 @@
+use csv;
+use serde_json;
+use failure::Error;
+use futures::future;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use eventbrite::model::{Event, Profile, Attende};
+use source::{AttendeeSource, SourceFuture};
+
+#[derive(Debug, Fail)]
+pub enum FileSourceError {
+    #[fail(display = "unsupported attendees file {}, expected a .csv or .json file", path)]
+    UnsupportedFormat {
+        path: String
+    },
+    #[fail(display = "error while loading attendees from {}", path)]
+    AttendeesLoadError {
+        path: String,
+        #[cause] cause: Error
+    },
+}
+
+#[derive(Debug, PartialEq)]
+enum Format {
+    Csv,
+    Json,
+}
+
+fn format(path: &Path) -> Result<Format, Error> {
+    match path.extension().and_then(|extension| extension.to_str()).map(|extension| extension.to_lowercase()) {
+        Some(ref extension) if extension == "csv" => Ok(Format::Csv),
+        Some(ref extension) if extension == "json" => Ok(Format::Json),
+        _ => Err(FileSourceError::UnsupportedFormat { path: path.display().to_string() }.into())
+    }
+}
+
+/// Line of an attendees file, `email` and `checked_in` are optional
+#[derive(Deserialize)]
+struct FileAttendee {
+    first_name: String,
+    last_name: String,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default)]
+    checked_in: bool,
+}
+
+impl From<FileAttendee> for Attende {
+    fn from(attendee: FileAttendee) -> Self {
+        let profile = Profile::new(attendee.first_name, attendee.last_name);
+        let attende = if attendee.checked_in { Attende::present(profile) } else { Attende::registered(profile) };
+        Attende { email: attendee.email.filter(|email| !email.is_empty()), ..attende }
+    }
+}
+
+/// Parse a CSV with a header line, the `first_name` and `last_name` columns are mandatory and other columns are ignored
+fn parse_csv<R: Read>(reader: R) -> Result<Vec<Attende>, Error> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader)
+        .deserialize()
+        .map(|attendee: Result<FileAttendee, csv::Error>| attendee.map(Attende::from))
+        .collect::<Result<Vec<Attende>, csv::Error>>()
+        .map_err(|error| error.into())
+}
+
+/// Parse a JSON array of objects with `first_name` and `last_name` fields
+fn parse_json<R: Read>(reader: R) -> Result<Vec<Attende>, Error> {
+    serde_json::from_reader(reader)
+        .map(|attendees: Vec<FileAttendee>| attendees.into_iter().map(Attende::from).collect())
+        .map_err(|error| error.into())
+}
+
+fn load_file(path: &Path) -> Result<Vec<Attende>, Error> {
+    let format = format(path)?;
+    File::open(path)
+        .map_err(|error| error.into())
+        .and_then(|file| match format {
+            Format::Csv => parse_csv(file),
+            Format::Json => parse_json(file),
+        })
+        .map_err(|cause| FileSourceError::AttendeesLoadError { path: path.display().to_string(), cause: cause }.into())
+}
+
+/// Attendees listed in a local CSV or JSON file, for events not managed on eventbrite
+///
+/// The file is read on each load so it can be edited while the application is running
+pub struct FileSource {
+    path: PathBuf,
+    event_id: String,
+}
+
+impl FileSource {
+    /// The event id defaults to the name of the file without its extension
+    pub fn new(path: PathBuf, event_id: Option<String>) -> Result<Self, Error> {
+        format(&path)?;
+        let event_id = event_id
+            .or(path.file_stem().and_then(|stem| stem.to_str()).map(|stem| stem.to_owned()))
+            .ok_or(FileSourceError::UnsupportedFormat { path: path.display().to_string() })?;
+        Ok(FileSource { path: path, event_id: event_id })
+    }
+}
+
+impl AttendeeSource for FileSource {
+    fn events(&self) -> SourceFuture<Vec<Event>> {
+        Box::new(future::ok(vec![Event::new(self.event_id.clone())]))
+    }
+
+    fn attendees(&self, _event: &Event) -> SourceFuture<Vec<Attende>> {
+        Box::new(future::result(load_file(&self.path)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::Future;
+    use std::env;
+    use std::fs;
+    use std::io::Write;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format(Path::new("attendees.csv")).unwrap(), Format::Csv);
+        assert_eq!(format(Path::new("/tmp/attendees.JSON")).unwrap(), Format::Json);
+        assert!(format(Path::new("attendees.txt")).is_err());
+        assert!(format(Path::new("attendees")).is_err());
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let content = "first_name,last_name,email,company\nFrancois, Teychene,francois@example.com,JUG\nFabien,Bernard,,JUG\n";
+        let actual = parse_csv(content.as_bytes());
+        let francois = Attende { email: Some("francois@example.com".to_string()), ..Attende::registered(Profile::new("Francois", "Teychene")) };
+        assert_eq!(actual.unwrap(), vec![francois, Attende::registered(Profile::new("Fabien", "Bernard"))]);
+
+        let content = "first_name,last_name,checked_in\nFrancois,Teychene,true\nFabien,Bernard,false\n";
+        let actual = parse_csv(content.as_bytes());
+        assert_eq!(actual.unwrap(), vec![Attende::present(Profile::new("Francois", "Teychene")), Attende::registered(Profile::new("Fabien", "Bernard"))]);
+
+        let actual = parse_csv("last_name\nTeychene\n".as_bytes());
+        assert!(actual.is_err());
+
+        let actual = parse_csv("first_name,last_name\n".as_bytes());
+        assert_eq!(actual.unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let content = r#"[{"first_name": "Francois", "last_name": "Teychene", "checked_in": true}, {"first_name": "Fabien", "last_name": "Bernard", "company": "JUG"}]"#;
+        let actual = parse_json(content.as_bytes());
+        assert_eq!(actual.unwrap(), vec![Attende::present(Profile::new("Francois", "Teychene")), Attende::registered(Profile::new("Fabien", "Bernard"))]);
+
+        let actual = parse_json(r#"{"first_name": "Francois"}"#.as_bytes());
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn test_file_source() {
+        let path = env::temp_dir().join("jug-lottery-test-meetup.csv");
+        let mut file = File::create(&path).unwrap();
+        file.write_all(b"first_name,last_name\nFrancois,Teychene\n").unwrap();
+
+        let source = FileSource::new(path.clone(), None).unwrap();
+        let event = source.events().wait().unwrap().remove(0);
+        assert_eq!(event, Event::new("jug-lottery-test-meetup".to_string()));
+        assert_eq!(source.attendees(&event).wait().unwrap(), vec![Attende::registered(Profile::new("Francois", "Teychene"))]);
+
+        let source = FileSource::new(path.clone(), Some("51124390428".to_string())).unwrap();
+        assert_eq!(source.events().wait().unwrap(), vec![Event::new("51124390428".to_string())]);
+        fs::remove_file(&path).unwrap();
+
+        let actual = source.attendees(&event).wait();
+        assert!(actual.is_err());
+        assert!(matches!(actual.unwrap_err().downcast::<FileSourceError>(), Ok(FileSourceError::AttendeesLoadError { .. })));
+
+        assert!(FileSource::new(env::temp_dir().join("attendees.txt"), None).is_err());
+    }
+}
@@ -5,9 +5,21 @@ extern crate failure_derive;
 extern crate serde_derive;
 extern crate serde;
 extern crate reqwest;
+extern crate futures;
+extern crate tokio_timer;
+extern crate chrono;
+#[macro_use]
+extern crate log;
 extern crate frunk;
 #[cfg(test)] #[macro_use] extern crate matches;
 extern crate rand;
+extern crate csv;
+extern crate serde_json;
 
+pub mod dedup;
 pub mod eventbrite;
-pub mod lottery;
\ No newline at end of file
+pub mod file;
+pub mod lottery;
+pub mod schedule;
+pub mod source;
+pub mod strategy;
\ No newline at end of file
@@ -1,6 +1,8 @@
 use eventbrite::model::Profile;
 use failure::Error;
-use rand::{seq, thread_rng};
+use rand::{thread_rng, Rng, SeedableRng};
+use rand::prng::ChaChaRng;
+use strategy::{DrawStrategy, Uniform};
 
 #[derive(Debug, Fail, PartialEq)]
 enum DrawError {
@@ -15,49 +17,149 @@ enum DrawError {
     }
 }
 
-pub fn draw(nb: i8, attendees: &Vec<Profile>) -> Result<Vec<&Profile>, Error> {
+/// Remove from the attendees list every person of the excluded list, by attendee id when known and by names otherwise
+pub fn exclude(attendees: &Vec<Profile>, excluded: &Vec<Profile>) -> Vec<Profile> {
+    attendees.iter()
+        .filter(|attendee| !excluded.iter().any(|excluded| excluded.same_person(attendee)))
+        .cloned()
+        .collect()
+}
+
+pub fn draw<'a, R: Rng>(nb: i8, attendees: &'a Vec<Profile>, rng: &mut R) -> Result<Vec<&'a Profile>, Error> {
+    draw_with(nb, attendees, &Uniform, rng)
+}
+
+/// Draw attendees using the given strategy to pick the winners
+pub fn draw_with<'a, R: Rng>(nb: i8, attendees: &'a Vec<Profile>, strategy: &dyn DrawStrategy, rng: &mut R) -> Result<Vec<&'a Profile>, Error> {
     match nb {
         a if a < 0 => Err(DrawError::InvalidDrawRequest { asked: a }.into()),
         0 => Ok(vec![]),
-        _ => {
-            let mut rng = thread_rng();
-            seq::sample_iter(&mut rng, attendees, nb as usize)
-                .map_err(|_| DrawError::NotEnoughtParticipant { asked: nb, existant: attendees.len() }.into())
-        }
+        a if a as usize > attendees.len() => Err(DrawError::NotEnoughtParticipant { asked: nb, existant: attendees.len() }.into()),
+        _ => Ok(strategy.sample(nb as usize, attendees, rng))
     }
 }
 
+/// Generate a new random seed for a draw
+pub fn new_seed() -> u64 {
+    thread_rng().gen()
+}
+
+/// Draw attendees with a RNG initialized from the seed
+///
+/// The same seed on the same attendees list always gives the same result, so a draw can be replayed
+pub fn seeded_draw(nb: i8, attendees: &Vec<Profile>, seed: u64) -> Result<Vec<&Profile>, Error> {
+    seeded_draw_with(nb, attendees, &Uniform, seed)
+}
+
+/// Draw attendees using the given strategy with a RNG initialized from the seed
+pub fn seeded_draw_with<'a>(nb: i8, attendees: &'a Vec<Profile>, strategy: &dyn DrawStrategy, seed: u64) -> Result<Vec<&'a Profile>, Error> {
+    let mut rng = ChaChaRng::seed_from_u64(seed);
+    draw_with(nb, attendees, strategy, &mut rng)
+}
+
+/// Compute a fingerprint (FNV-1a 64 bits) of the attendees list
+///
+/// The order of the attendees is part of the fingerprint since it changes the result of a seeded draw.
+/// The attendee id, when known, tells apart the namesakes
+pub fn attendees_hash(attendees: &Vec<Profile>) -> String {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+
+    let hash = attendees.iter()
+        .flat_map(|attendee| {
+            let id = attendee.id.iter().flat_map(|id| Some(0x1f).into_iter().chain(id.bytes()));
+            attendee.first_name.bytes().chain(Some(0x1f)).chain(attendee.last_name.bytes()).chain(id).chain(Some(0x1e))
+        })
+        .fold(FNV_OFFSET_BASIS, |hash, byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME));
+    format!("{:016x}", hash)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_draw() {
-        let attendees = vec![Profile { first_name: "Francois".to_string(), last_name: "Teychene".to_string() }];
-        let actual = draw(1, attendees.as_ref());
+        let attendees = vec![Profile::new("Francois".to_string(), "Teychene".to_string())];
+        let actual = draw(1, attendees.as_ref(), &mut thread_rng());
         assert!(actual.is_ok());
-        assert_eq!(actual.unwrap().as_slice(), vec![&Profile { first_name: "Francois".to_string(), last_name: "Teychene".to_string() }].as_slice());
+        assert_eq!(actual.unwrap().as_slice(), vec![&Profile::new("Francois".to_string(), "Teychene".to_string())].as_slice());
 
-        let attendees = vec![Profile { first_name: "Francois".to_string(), last_name: "Teychene".to_string() }];
-        let actual = draw(40, attendees.as_ref());
+        let attendees = vec![Profile::new("Francois".to_string(), "Teychene".to_string())];
+        let actual = draw(40, attendees.as_ref(), &mut thread_rng());
         assert!(actual.is_err());
         assert_eq!(actual.unwrap_err().downcast::<DrawError>().unwrap(), DrawError::NotEnoughtParticipant { asked: 40, existant: 1 });
 
-        let attendees = vec![Profile { first_name: "Francois".to_string(), last_name: "Teychene".to_string() }];
-        let actual = draw(-1, attendees.as_ref());
+        let attendees = vec![Profile::new("Francois".to_string(), "Teychene".to_string())];
+        let actual = draw(-1, attendees.as_ref(), &mut thread_rng());
         assert!(actual.is_err());
         assert_eq!(actual.unwrap_err().downcast::<DrawError>().unwrap(), DrawError::InvalidDrawRequest {asked: -1});
 
-        let attendees = vec![Profile { first_name: "Francois".to_string(), last_name: "Teychene".to_string() }];
-        let actual = draw(-50, attendees.as_ref());
+        let attendees = vec![Profile::new("Francois".to_string(), "Teychene".to_string())];
+        let actual = draw(-50, attendees.as_ref(), &mut thread_rng());
         assert!(actual.is_err());
         assert_eq!(actual.unwrap_err().downcast::<DrawError>().unwrap(), DrawError::InvalidDrawRequest {asked: -50});
 
-        let attendees = vec![Profile { first_name: "Francois".to_string(), last_name: "Teychene".to_string() }, Profile { first_name: "Fabien".to_string(), last_name: "Bernard".to_string() }];
-        let actual = draw(0, &attendees);
+        let attendees = vec![Profile::new("Francois".to_string(), "Teychene".to_string()), Profile::new("Fabien".to_string(), "Bernard".to_string())];
+        let actual = draw(0, &attendees, &mut thread_rng());
         assert!(actual.is_ok());
         let vec : Vec<&Profile> = Vec::new();
         assert_eq!(actual.unwrap().as_slice(), vec.as_slice());
 
     }
+
+    #[test]
+    fn test_seeded_draw() {
+        let attendees = vec![
+            Profile::new("Francois".to_string(), "Teychene".to_string()),
+            Profile::new("Fabien".to_string(), "Bernard".to_string()),
+            Profile::new("Jean-Luc".to_string(), "Racine".to_string()),
+            Profile::new("Renard".to_string(), "Chenapan".to_string())];
+        let first = seeded_draw(2, &attendees, 42).unwrap();
+        let second = seeded_draw(2, &attendees, 42).unwrap();
+        assert_eq!(first.len(), 2);
+        assert_eq!(first, second);
+
+        let actual = seeded_draw(5, &attendees, 42);
+        assert!(actual.is_err());
+        assert_eq!(actual.unwrap_err().downcast::<DrawError>().unwrap(), DrawError::NotEnoughtParticipant { asked: 5, existant: 4 });
+    }
+
+    #[test]
+    fn test_attendees_hash() {
+        let attendees = vec![Profile::new("Francois".to_string(), "Teychene".to_string()), Profile::new("Fabien".to_string(), "Bernard".to_string())];
+        let reversed = vec![Profile::new("Fabien".to_string(), "Bernard".to_string()), Profile::new("Francois".to_string(), "Teychene".to_string())];
+        assert_eq!(attendees_hash(&attendees), attendees_hash(&attendees.clone()));
+        assert_eq!(attendees_hash(&attendees).len(), 16);
+        assert_ne!(attendees_hash(&attendees), attendees_hash(&reversed));
+        assert_eq!(attendees_hash(&vec![]), "cbf29ce484222325");
+
+        let split_differently = vec![Profile::new("Fran".to_string(), "coisTeychene".to_string())];
+        let single = vec![Profile::new("Francois".to_string(), "Teychene".to_string())];
+        assert_ne!(attendees_hash(&single), attendees_hash(&split_differently));
+
+        let with_id = |id: &str| Profile { id: Some(id.to_string()), ..Profile::new("Francois", "Teychene") };
+        assert_ne!(attendees_hash(&vec![with_id("1060946531")]), attendees_hash(&vec![with_id("1060946536")]));
+        assert_ne!(attendees_hash(&vec![with_id("1060946531")]), attendees_hash(&single));
+        assert_eq!(attendees_hash(&vec![with_id("1060946531")]), attendees_hash(&vec![with_id("1060946531")]));
+    }
+
+    #[test]
+    fn test_exclude() {
+        let attendees = vec![Profile::new("Francois".to_string(), "Teychene".to_string()), Profile::new("Fabien".to_string(), "Bernard".to_string())];
+        let excluded = vec![Profile::new("Fabien".to_string(), "Bernard".to_string())];
+        assert_eq!(exclude(&attendees, &excluded), vec![Profile::new("Francois".to_string(), "Teychene".to_string())]);
+
+        let excluded = vec![];
+        assert_eq!(exclude(&attendees, &excluded), attendees);
+
+        let excluded = vec![Profile::new("Fabien".to_string(), "Teychene".to_string())];
+        assert_eq!(exclude(&attendees, &excluded), attendees);
+
+        // Namesakes told apart by their attendee id
+        let francois = |id: &str| Profile { id: Some(id.to_string()), ..Profile::new("Francois".to_string(), "Teychene".to_string()) };
+        let attendees = vec![francois("1060946531"), francois("1060946536")];
+        assert_eq!(exclude(&attendees, &vec![francois("1060946536")]), vec![francois("1060946531")]);
+        assert_eq!(exclude(&attendees, &vec![Profile::new("Francois".to_string(), "Teychene".to_string())]), vec![]);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,94 @@
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use eventbrite::model::{Event, EventDate};
+use std::time::Duration;
+
+/// Delays between two updates of the attendees cache
+///
+/// The attendees are refreshed often around the live events, from `window` before their start to `window` after their end,
+/// and rarely otherwise. Events without a start date (loaded from a file) are always considered running
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefreshSchedule {
+    /// Delay used around the events
+    pub fast: Duration,
+    /// Delay used far from the events
+    pub slow: Duration,
+    pub window: Duration,
+}
+
+impl Default for RefreshSchedule {
+    fn default() -> Self {
+        RefreshSchedule { fast: Duration::from_secs(10), slow: Duration::from_secs(600), window: Duration::from_secs(7200) }
+    }
+}
+
+fn parse_date(date: &Option<EventDate>) -> Option<DateTime<Utc>> {
+    date.as_ref().and_then(|date| date.utc.parse().ok())
+}
+
+impl RefreshSchedule {
+    /// Period of an event during which the attendees are refreshed often, None if the event has no start date
+    fn fast_period(&self, event: &Event) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let window = ChronoDuration::from_std(self.window).unwrap_or_else(|_| ChronoDuration::zero());
+        parse_date(&event.start).map(|start| {
+            let end = parse_date(&event.end).unwrap_or(start);
+            (start - window, end + window)
+        })
+    }
+
+    /// Delay before the next update, never longer than the time left before the fast period of an event
+    pub fn next_delay(&self, events: &[Event], now: DateTime<Utc>) -> Duration {
+        events.iter()
+            .map(|event| match self.fast_period(event) {
+                None => self.fast,
+                Some((from, to)) if from <= now && now <= to => self.fast,
+                Some((from, _)) if now < from => (from - now).to_std().unwrap_or(self.fast).max(self.fast),
+                Some(_) => self.slow
+            })
+            .fold(self.slow, |delay, event_delay| delay.min(event_delay))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(utc: &str) -> Option<EventDate> {
+        Some(EventDate { timezone: "Europe/Paris".to_string(), local: utc.trim_end_matches('Z').to_string(), utc: utc.to_string() })
+    }
+
+    fn event(start: &str, end: &str) -> Event {
+        Event { start: date(start), end: date(end), ..Event::new("51124390428".to_string()) }
+    }
+
+    fn now(utc: &str) -> DateTime<Utc> {
+        utc.parse().unwrap()
+    }
+
+    #[test]
+    fn test_next_delay() {
+        let schedule = RefreshSchedule { fast: Duration::from_secs(10), slow: Duration::from_secs(600), window: Duration::from_secs(3600) };
+        let events = vec![event("2018-11-21T18:00:00Z", "2018-11-21T21:00:00Z")];
+
+        assert_eq!(schedule.next_delay(&events, now("2018-11-20T12:00:00Z")), Duration::from_secs(600));
+        assert_eq!(schedule.next_delay(&events, now("2018-11-21T16:55:00Z")), Duration::from_secs(300));
+        assert_eq!(schedule.next_delay(&events, now("2018-11-21T16:59:55Z")), Duration::from_secs(10));
+        assert_eq!(schedule.next_delay(&events, now("2018-11-21T17:00:00Z")), Duration::from_secs(10));
+        assert_eq!(schedule.next_delay(&events, now("2018-11-21T19:30:00Z")), Duration::from_secs(10));
+        assert_eq!(schedule.next_delay(&events, now("2018-11-21T22:00:00Z")), Duration::from_secs(10));
+        assert_eq!(schedule.next_delay(&events, now("2018-11-21T22:00:01Z")), Duration::from_secs(600));
+
+        let events = vec![event("2018-11-21T18:00:00Z", "2018-11-21T21:00:00Z"), event("2018-12-19T18:00:00Z", "2018-12-19T21:00:00Z")];
+        assert_eq!(schedule.next_delay(&events, now("2018-11-21T19:30:00Z")), Duration::from_secs(10));
+        assert_eq!(schedule.next_delay(&events, now("2018-11-30T19:30:00Z")), Duration::from_secs(600));
+    }
+
+    #[test]
+    fn test_next_delay_without_dates() {
+        let schedule = RefreshSchedule::default();
+        assert_eq!(schedule.next_delay(&[], Utc::now()), schedule.slow);
+        assert_eq!(schedule.next_delay(&[Event::new("meetup".to_string())], Utc::now()), schedule.fast);
+
+        let invalid = Event { start: date("tomorrow"), ..Event::new("meetup".to_string()) };
+        assert_eq!(schedule.next_delay(&[invalid], Utc::now()), schedule.fast);
+    }
+}
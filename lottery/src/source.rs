@@ -0,0 +1,24 @@
+use eventbrite::model::{Event, Attende};
+use failure::Error;
+use futures::Future;
+
+/// Asynchronous result of an attendee source
+pub type SourceFuture<T> = Box<dyn Future<Item=T, Error=Error>>;
+
+/// Provider of the live events and of their attendees
+pub trait AttendeeSource {
+    /// Live events, the first one is the default event
+    fn events(&self) -> SourceFuture<Vec<Event>>;
+
+    fn attendees(&self, event: &Event) -> SourceFuture<Vec<Attende>>;
+}
+
+impl<S: AttendeeSource + ?Sized> AttendeeSource for Box<S> {
+    fn events(&self) -> SourceFuture<Vec<Event>> {
+        (**self).events()
+    }
+
+    fn attendees(&self, event: &Event) -> SourceFuture<Vec<Attende>> {
+        (**self).attendees(event)
+    }
+}
@@ -0,0 +1,123 @@
+use eventbrite::model::Profile;
+use rand::{seq, Rng, RngCore};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Way to pick the winners among the attendees
+pub trait DrawStrategy {
+    /// Pick `nb` attendees, `nb` is never greater than the number of attendees
+    fn sample<'a>(&self, nb: usize, attendees: &'a Vec<Profile>, rng: &mut dyn RngCore) -> Vec<&'a Profile>;
+}
+
+/// Every attendee has the same chance to win
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Uniform;
+
+impl DrawStrategy for Uniform {
+    fn sample<'a>(&self, nb: usize, attendees: &'a Vec<Profile>, rng: &mut dyn RngCore) -> Vec<&'a Profile> {
+        seq::sample_iter(rng, attendees, nb).unwrap_or_else(|all| all)
+    }
+}
+
+/// Attendance history of a person : number of events attended and number of events won
+#[derive(Debug, Clone, PartialEq)]
+pub struct Participation {
+    pub profile: Profile,
+    pub attended: u32,
+    pub won: u32,
+}
+
+impl Participation {
+    /// Weight grows with the attended events and is divided by the won ones
+    ///
+    /// A newcomer has a weight of 1, a regular who never won has the highest weight
+    pub fn weight(&self) -> f64 {
+        (self.attended + 1) as f64 / (self.won + 1) as f64
+    }
+}
+
+/// Attendees have a chance to win proportional to their weight
+///
+/// Attendees without a known weight have a weight of 1. Weights must be strictly positive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Weighted {
+    weights: HashMap<Profile, f64>
+}
+
+const DEFAULT_WEIGHT: f64 = 1.0;
+
+impl Weighted {
+    pub fn new(weights: HashMap<Profile, f64>) -> Weighted {
+        Weighted { weights: weights }
+    }
+
+    pub fn from_history(history: &Vec<Participation>) -> Weighted {
+        Weighted::new(history.iter().map(|participation| (participation.profile.clone(), participation.weight())).collect())
+    }
+
+    /// Weight of the attendee, also looked up without the attendee id since the history is recorded by names
+    pub fn weight(&self, attendee: &Profile) -> f64 {
+        *self.weights.get(attendee)
+            .or_else(|| self.weights.get(&attendee.without_id()))
+            .unwrap_or(&DEFAULT_WEIGHT)
+    }
+}
+
+impl DrawStrategy for Weighted {
+    /// Weighted sampling without replacement (Efraimidis-Spirakis) : each attendee get a key `u^(1/weight)`
+    /// with `u` uniformly random in `[0, 1)` and the attendees with the highest keys win
+    fn sample<'a>(&self, nb: usize, attendees: &'a Vec<Profile>, rng: &mut dyn RngCore) -> Vec<&'a Profile> {
+        let mut keyed: Vec<(f64, &Profile)> = attendees.iter()
+            .map(|attendee| {
+                let random: f64 = rng.gen();
+                (random.powf(1.0 / self.weight(attendee)), attendee)
+            })
+            .collect();
+        keyed.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+        keyed.into_iter().take(nb).map(|(_, attendee)| attendee).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{SeedableRng, thread_rng};
+    use rand::prng::ChaChaRng;
+
+    #[test]
+    fn test_participation_weight() {
+        assert_eq!(Participation { profile: Profile::new("Francois", "Teychene"), attended: 0, won: 0 }.weight(), 1.0);
+        assert_eq!(Participation { profile: Profile::new("Francois", "Teychene"), attended: 5, won: 0 }.weight(), 6.0);
+        assert_eq!(Participation { profile: Profile::new("Francois", "Teychene"), attended: 5, won: 2 }.weight(), 2.0);
+    }
+
+    #[test]
+    fn test_uniform() {
+        let attendees = vec![Profile::new("Francois", "Teychene"), Profile::new("Fabien", "Bernard")];
+        assert_eq!(Uniform.sample(2, &attendees, &mut thread_rng()).len(), 2);
+        assert_eq!(Uniform.sample(1, &attendees, &mut thread_rng()).len(), 1);
+        assert_eq!(Uniform.sample(0, &attendees, &mut thread_rng()).len(), 0);
+    }
+
+    #[test]
+    fn test_weighted() {
+        let attendees = vec![Profile::new("Francois", "Teychene"), Profile::new("Fabien", "Bernard"), Profile::new("Jean-Luc", "Racine")];
+        let strategy = Weighted::from_history(&vec![Participation { profile: Profile::new("Fabien", "Bernard"), attended: 999, won: 0 }]);
+        assert_eq!(strategy.weight(&Profile::new("Fabien", "Bernard")), 1000.0);
+        assert_eq!(strategy.weight(&Profile::new("Francois", "Teychene")), 1.0);
+        assert_eq!(strategy.weight(&Profile { id: Some("1060946532".to_string()), ..Profile::new("Fabien", "Bernard") }), 1000.0);
+
+        let favoured = (0..100)
+            .filter(|seed| strategy.sample(1, &attendees, &mut ChaChaRng::seed_from_u64(*seed)) == vec![&Profile::new("Fabien", "Bernard")])
+            .count();
+        assert!(favoured > 90);
+
+        let mut all = strategy.sample(3, &attendees, &mut thread_rng());
+        all.sort_by_key(|attendee| attendee.first_name.clone());
+        assert_eq!(all, vec![&Profile::new("Fabien", "Bernard"), &Profile::new("Francois", "Teychene"), &Profile::new("Jean-Luc", "Racine")]);
+
+        let first = strategy.sample(2, &attendees, &mut ChaChaRng::seed_from_u64(42));
+        let second = strategy.sample(2, &attendees, &mut ChaChaRng::seed_from_u64(42));
+        assert_eq!(first, second);
+    }
+}
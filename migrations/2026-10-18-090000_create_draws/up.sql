@@ -0,0 +1,9 @@
+CREATE TABLE draws (
+  id VARCHAR NOT NULL PRIMARY KEY,
+  event_id VARCHAR NOT NULL,
+  seed VARCHAR NOT NULL,
+  attendees_hash VARCHAR NOT NULL,
+  nb INTEGER NOT NULL,
+  attendees TEXT NOT NULL,
+  result TEXT NOT NULL
+)
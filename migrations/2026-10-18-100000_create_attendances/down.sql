@@ -0,0 +1,14 @@
+DROP TABLE attendances;
+
+CREATE TABLE draws_backup (
+  id VARCHAR NOT NULL PRIMARY KEY,
+  event_id VARCHAR NOT NULL,
+  seed VARCHAR NOT NULL,
+  attendees_hash VARCHAR NOT NULL,
+  nb INTEGER NOT NULL,
+  attendees TEXT NOT NULL,
+  result TEXT NOT NULL
+);
+INSERT INTO draws_backup SELECT id, event_id, seed, attendees_hash, nb, attendees, result FROM draws;
+DROP TABLE draws;
+ALTER TABLE draws_backup RENAME TO draws;
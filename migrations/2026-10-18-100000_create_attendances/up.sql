@@ -0,0 +1,8 @@
+CREATE TABLE attendances (
+  id VARCHAR NOT NULL PRIMARY KEY,
+  event_id VARCHAR NOT NULL,
+  first_name VARCHAR NOT NULL,
+  last_name VARCHAR NOT NULL
+);
+
+ALTER TABLE draws ADD COLUMN weights TEXT;
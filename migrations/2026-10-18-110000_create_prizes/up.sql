@@ -0,0 +1,9 @@
+CREATE TABLE prizes (
+  id VARCHAR NOT NULL PRIMARY KEY,
+  name VARCHAR NOT NULL,
+  sponsor VARCHAR,
+  quantity INTEGER NOT NULL,
+  event_id VARCHAR NOT NULL
+);
+
+ALTER TABLE winners ADD COLUMN prize_id VARCHAR REFERENCES prizes(id);
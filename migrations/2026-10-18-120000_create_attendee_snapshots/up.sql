@@ -0,0 +1,7 @@
+CREATE TABLE attendee_snapshots (
+  event_id VARCHAR NOT NULL PRIMARY KEY,
+  position INTEGER NOT NULL,
+  event TEXT NOT NULL,
+  attendees TEXT NOT NULL,
+  loaded_at TIMESTAMP NOT NULL
+);
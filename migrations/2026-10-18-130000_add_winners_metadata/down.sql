@@ -0,0 +1,10 @@
+CREATE TABLE winners_backup (
+  id VARCHAR NOT NULL PRIMARY KEY,
+  first_name VARCHAR NOT NULL,
+  last_name VARCHAR NOT NULL,
+  event_id VARCHAR NOT NULL,
+  prize_id VARCHAR REFERENCES prizes(id)
+);
+INSERT INTO winners_backup SELECT id, first_name, last_name, event_id, prize_id FROM winners;
+DROP TABLE winners;
+ALTER TABLE winners_backup RENAME TO winners;
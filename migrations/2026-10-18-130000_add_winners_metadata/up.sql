@@ -0,0 +1,4 @@
+ALTER TABLE winners ADD COLUMN created_at TIMESTAMP;
+ALTER TABLE winners ADD COLUMN draw_id VARCHAR REFERENCES draws(id);
+ALTER TABLE winners ADD COLUMN prize VARCHAR;
+ALTER TABLE winners ADD COLUMN recorded_by VARCHAR;
@@ -0,0 +1,14 @@
+CREATE TABLE winners_backup (
+  id VARCHAR NOT NULL PRIMARY KEY,
+  first_name VARCHAR NOT NULL,
+  last_name VARCHAR NOT NULL,
+  event_id VARCHAR NOT NULL,
+  prize_id VARCHAR REFERENCES prizes(id),
+  created_at TIMESTAMP,
+  draw_id VARCHAR REFERENCES draws(id),
+  prize VARCHAR,
+  recorded_by VARCHAR
+);
+INSERT INTO winners_backup SELECT id, first_name, last_name, event_id, prize_id, created_at, draw_id, prize, recorded_by FROM winners;
+DROP TABLE winners;
+ALTER TABLE winners_backup RENAME TO winners;
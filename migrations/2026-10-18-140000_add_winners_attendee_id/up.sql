@@ -0,0 +1 @@
+ALTER TABLE winners ADD COLUMN attendee_id VARCHAR;
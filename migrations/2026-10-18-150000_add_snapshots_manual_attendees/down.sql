@@ -0,0 +1,10 @@
+CREATE TABLE attendee_snapshots_backup (
+  event_id VARCHAR NOT NULL PRIMARY KEY,
+  position INTEGER NOT NULL,
+  event TEXT NOT NULL,
+  attendees TEXT NOT NULL,
+  loaded_at TIMESTAMP NOT NULL
+);
+INSERT INTO attendee_snapshots_backup SELECT event_id, position, event, attendees, loaded_at FROM attendee_snapshots;
+DROP TABLE attendee_snapshots;
+ALTER TABLE attendee_snapshots_backup RENAME TO attendee_snapshots;
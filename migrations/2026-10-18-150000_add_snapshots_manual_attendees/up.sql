@@ -0,0 +1 @@
+ALTER TABLE attendee_snapshots ADD COLUMN manual TEXT NOT NULL DEFAULT '[]';